@@ -0,0 +1,166 @@
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tauri::{command, AppHandle, Manager, State};
+
+/// What a draft will become once it's sent.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum DraftTarget {
+    Comment { issue_number: i64 },
+    CommentEdit { issue_number: i64, comment_id: i64 },
+    Issue,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Draft {
+    /// `owner/repo`
+    pub repo: String,
+    pub target: DraftTarget,
+    /// Only used by drafts of new issues.
+    pub title: Option<String>,
+    pub body: String,
+    pub updated_at: String,
+}
+
+impl Draft {
+    fn is_for(&self, repo: &str, target: &DraftTarget) -> bool {
+        self.repo.eq_ignore_ascii_case(repo) && self.target == *target
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Drafts {
+    drafts: Vec<Draft>,
+}
+
+/// Serializes reads and writes of the drafts file, autosaves can arrive in quick succession.
+#[derive(Debug, Default)]
+pub struct DraftStore {
+    lock: Mutex<()>,
+}
+
+fn get_drafts_path(app: &AppHandle) -> Result<PathBuf, String> {
+    let config_dir = app
+        .path()
+        .app_config_dir()
+        .expect("failed to get config dir");
+
+    fs::create_dir_all(&config_dir).map_err(|e| e.to_string())?;
+
+    Ok(config_dir.join("drafts.json"))
+}
+
+fn read_drafts(app: &AppHandle) -> Result<Drafts, String> {
+    match fs::read_to_string(get_drafts_path(app)?) {
+        Ok(contents) => {
+            serde_json::from_str(&contents).map_err(|e| format!("Failed to parse drafts: {}", e))
+        }
+        Err(_) => Ok(Drafts::default()),
+    }
+}
+
+fn write_drafts(app: &AppHandle, drafts: &Drafts) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(drafts)
+        .map_err(|e| format!("Failed to serialize drafts: {}", e))?;
+    fs::write(get_drafts_path(app)?, json).map_err(|e| format!("Failed to write drafts: {}", e))
+}
+
+impl DraftStore {
+    fn update<T>(
+        &self,
+        app: &AppHandle,
+        change: impl FnOnce(&mut Drafts) -> T,
+    ) -> Result<T, String> {
+        let _guard = self.lock.lock().map_err(|e| e.to_string())?;
+        let mut drafts = read_drafts(app)?;
+        let result = change(&mut drafts);
+        write_drafts(app, &drafts)?;
+        Ok(result)
+    }
+
+    fn remove(&self, app: &AppHandle, repo: &str, target: &DraftTarget) -> Result<(), String> {
+        self.update(app, |drafts| {
+            drafts.drafts.retain(|draft| !draft.is_for(repo, target))
+        })
+    }
+}
+
+/// Drops the draft for a write that went through. Failures are only logged, the write
+/// itself already succeeded.
+pub fn clear_draft(app: &AppHandle, repo: &str, target: DraftTarget) {
+    if let Err(e) = app.state::<DraftStore>().remove(app, repo, &target) {
+        println!("Failed to clear draft for {}: {}", repo, e);
+    }
+}
+
+/// Saves the text being written. An empty draft is removed instead.
+#[command]
+pub async fn save_draft(
+    app: AppHandle,
+    repo: String,
+    target: DraftTarget,
+    title: Option<String>,
+    body: String,
+    store: State<'_, DraftStore>,
+) -> Result<Option<Draft>, String> {
+    let title = title.filter(|title| !title.trim().is_empty());
+    if title.is_none() && body.trim().is_empty() {
+        store.remove(&app, &repo, &target)?;
+        return Ok(None);
+    }
+
+    let draft = Draft {
+        repo,
+        target,
+        title,
+        body,
+        updated_at: Utc::now().to_rfc3339(),
+    };
+    store.update(&app, |drafts| {
+        drafts
+            .drafts
+            .retain(|existing| !existing.is_for(&draft.repo, &draft.target));
+        drafts.drafts.push(draft.clone());
+    })?;
+
+    Ok(Some(draft))
+}
+
+#[command]
+pub async fn get_draft(
+    app: AppHandle,
+    repo: String,
+    target: DraftTarget,
+    store: State<'_, DraftStore>,
+) -> Result<Option<Draft>, String> {
+    let _guard = store.lock.lock().map_err(|e| e.to_string())?;
+    Ok(read_drafts(&app)?
+        .drafts
+        .into_iter()
+        .find(|draft| draft.is_for(&repo, &target)))
+}
+
+/// Every saved draft, most recently edited first.
+#[command]
+pub async fn list_drafts(
+    app: AppHandle,
+    store: State<'_, DraftStore>,
+) -> Result<Vec<Draft>, String> {
+    let _guard = store.lock.lock().map_err(|e| e.to_string())?;
+    let mut drafts = read_drafts(&app)?.drafts;
+    drafts.sort_by(|a, b| b.updated_at.cmp(&a.updated_at));
+    Ok(drafts)
+}
+
+#[command]
+pub async fn discard_draft(
+    app: AppHandle,
+    repo: String,
+    target: DraftTarget,
+    store: State<'_, DraftStore>,
+) -> Result<(), String> {
+    store.remove(&app, &repo, &target)
+}
@@ -0,0 +1,223 @@
+use super::github_client::get_client;
+use super::issues::{CommentData, IssueData, IssuesCache, ReactionSummary};
+use super::rate_limit::{all_pages, refresh_untracked_quotas, with_retry, Resource};
+use super::tasks::parse_tasks;
+use octocrab::models::issues::Comment;
+use octocrab::{Octocrab, Page};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tauri::{command, State};
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+
+/// How many issues have their comments loaded at once during a prefetch.
+const COMMENT_PREFETCH_CONCURRENCY: usize = 6;
+
+impl From<Comment> for CommentData {
+    fn from(comment: Comment) -> Self {
+        CommentData {
+            id: comment.id.0.try_into().unwrap_or_default(),
+            tasks: parse_tasks(comment.body.as_deref()),
+            body: comment.body.unwrap_or_default(),
+            created_at: comment.created_at.to_rfc3339(),
+            updated_at: comment.updated_at.map(|t| t.to_rfc3339()),
+            author: comment.user.login,
+            reactions: None,
+            pending: false,
+        }
+    }
+}
+
+/// A comment as the REST API returns it, including its reaction counts.
+#[derive(Debug, Deserialize)]
+pub struct RestComment {
+    #[serde(flatten)]
+    pub comment: Comment,
+    pub reactions: Option<ReactionSummary>,
+}
+
+impl From<RestComment> for CommentData {
+    fn from(rest: RestComment) -> Self {
+        CommentData {
+            reactions: rest.reactions,
+            ..CommentData::from(rest.comment)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CommentLoadResult {
+    pub issue_number: i64,
+    pub comment_count: usize,
+    pub error: Option<String>,
+}
+
+/// Loads every page of an issue's comments.
+pub async fn load_comments(
+    octocrab: &Octocrab,
+    owner: &str,
+    repo: &str,
+    issue_number: i64,
+) -> Result<Vec<CommentData>, String> {
+    // Requested directly because the typed builder drops the reaction counts
+    let route = format!("/repos/{}/{}/issues/{}/comments", owner, repo, issue_number);
+    let page: Page<RestComment> = with_retry(Resource::Core, || {
+        octocrab.get(&route, Some(&[("per_page", 100)]))
+    })
+    .await?;
+    let comments = all_pages(octocrab, page).await?;
+
+    Ok(comments.into_iter().map(CommentData::from).collect())
+}
+
+/// Loads comments for the given issues with bounded parallelism. A failure is recorded on
+/// that issue only and doesn't stop the others.
+pub async fn prefetch_comments_for(
+    cache: &IssuesCache,
+    octocrab: &Octocrab,
+    owner: &str,
+    repo: &str,
+    issue_numbers: Vec<i64>,
+) -> Vec<CommentLoadResult> {
+    let cache_key = format!("{}/{}", owner, repo);
+
+    // Remember when each issue was last updated so late results can't mark newer issues loaded
+    let pending: Vec<(i64, String)> = match cache.get_cache().lock() {
+        Ok(cache_guard) => cache_guard
+            .get(&cache_key)
+            .map(|cached| {
+                cached
+                    .issues
+                    .iter()
+                    .filter(|issue| issue_numbers.contains(&issue.number))
+                    .map(|issue| (issue.number, issue.updated_at.clone()))
+                    .collect()
+            })
+            .unwrap_or_default(),
+        Err(e) => {
+            println!("Failed to read issues cache: {}", e);
+            return Vec::new();
+        }
+    };
+
+    let semaphore = Arc::new(Semaphore::new(COMMENT_PREFETCH_CONCURRENCY));
+    let mut tasks = JoinSet::new();
+    for (issue_number, updated_at) in pending {
+        let semaphore = semaphore.clone();
+        let octocrab = octocrab.clone();
+        let owner = owner.to_string();
+        let repo = repo.to_string();
+
+        tasks.spawn(async move {
+            let _permit = semaphore.acquire_owned().await;
+            let comments = load_comments(&octocrab, &owner, &repo, issue_number).await;
+            (issue_number, updated_at, comments)
+        });
+    }
+
+    let mut results = Vec::new();
+    while let Some(joined) = tasks.join_next().await {
+        let (issue_number, updated_at, comments) = match joined {
+            Ok(loaded) => loaded,
+            Err(e) => {
+                println!("Comment prefetch task failed: {}", e);
+                continue;
+            }
+        };
+
+        let result = CommentLoadResult {
+            issue_number,
+            comment_count: comments.as_ref().map(Vec::len).unwrap_or_default(),
+            error: comments.as_ref().err().cloned(),
+        };
+        if let Some(e) = &result.error {
+            println!("Error fetching comments for issue #{}: {}", issue_number, e);
+        }
+
+        if let Err(e) = cache.set_issue_comments(&cache_key, issue_number, &updated_at, comments) {
+            println!("Failed to store comments for #{}: {}", issue_number, e);
+        }
+        results.push(result);
+    }
+
+    // Typed octocrab calls don't record the quota they used
+    refresh_untracked_quotas().await;
+
+    results.sort_by_key(|result| result.issue_number);
+    results
+}
+
+/// Returns an issue with its comments, loading them unless the cached ones are current.
+#[command]
+pub async fn fetch_issue_comments(
+    owner: String,
+    repo: String,
+    issue_number: i64,
+    force_refresh: bool,
+    cache: State<'_, IssuesCache>,
+) -> Result<IssueData, String> {
+    let cache_key = format!("{}/{}", owner, repo);
+
+    let cached_issue = {
+        let cache_guard = cache.get_cache().lock().map_err(|e| e.to_string())?;
+        cache_guard
+            .get(&cache_key)
+            .and_then(|cached| cached.issues.iter().find(|i| i.number == issue_number))
+            .cloned()
+    };
+    let Some(cached_issue) = cached_issue else {
+        return Err(format!(
+            "Issue #{} is not cached for {}",
+            issue_number, cache_key
+        ));
+    };
+    if cached_issue.comments_loaded && !force_refresh {
+        return Ok(cached_issue);
+    }
+
+    let octocrab = get_client()?;
+    let comments = load_comments(&octocrab, &owner, &repo, issue_number).await;
+    let failed = comments.as_ref().err().cloned();
+
+    let issue = cache
+        .set_issue_comments(&cache_key, issue_number, &cached_issue.updated_at, comments)?
+        .unwrap_or(cached_issue);
+
+    // Hand back stored comments when offline, with the error recorded on the issue
+    match failed {
+        Some(e) if issue.comments.is_empty() && issue.comments_count > 0 => Err(e),
+        _ => Ok(issue),
+    }
+}
+
+/// Loads comments for the given issues, or every issue whose comments are stale.
+#[command]
+pub async fn prefetch_issue_comments(
+    owner: String,
+    repo: String,
+    issue_numbers: Option<Vec<i64>>,
+    cache: State<'_, IssuesCache>,
+) -> Result<Vec<CommentLoadResult>, String> {
+    let cache_key = format!("{}/{}", owner, repo);
+
+    let issue_numbers = match issue_numbers {
+        Some(issue_numbers) => issue_numbers,
+        None => {
+            let cache_guard = cache.get_cache().lock().map_err(|e| e.to_string())?;
+            cache_guard
+                .get(&cache_key)
+                .map(|cached| {
+                    cached
+                        .issues
+                        .iter()
+                        .filter(|issue| !issue.comments_loaded)
+                        .map(|issue| issue.number)
+                        .collect()
+                })
+                .unwrap_or_default()
+        }
+    };
+
+    let octocrab = get_client()?;
+    Ok(prefetch_comments_for(&cache, &octocrab, &owner, &repo, issue_numbers).await)
+}
@@ -0,0 +1,327 @@
+use super::issues::{CachedIssues, IssuesCache};
+use super::pulls::{CachedPullRequests, PullRequestsCache};
+use super::references::{parse_references, IssueRef, ReferenceKind};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use tauri::{command, State};
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
+pub struct IssueKey {
+    pub repo: String,
+    pub number: i64,
+}
+
+/// An issue or PR in the graph. Referenced issues that aren't cached have no details.
+#[derive(Debug, Clone, Serialize)]
+pub struct Node {
+    pub repo: String,
+    pub number: i64,
+    pub title: Option<String>,
+    pub state: Option<String>,
+    pub is_pull_request: bool,
+    pub cached: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Edge {
+    pub from: IssueKey,
+    pub to: IssueKey,
+    pub kind: ReferenceKind,
+    /// The comment the reference was found in, `None` for the body.
+    pub comment_id: Option<i64>,
+}
+
+/// The references between every cached issue and PR.
+#[derive(Debug, Default)]
+pub struct ReferenceGraph {
+    nodes: HashMap<IssueKey, Node>,
+    edges: Vec<Edge>,
+    // Lowercased repo names to the spelling of their cache key
+    repo_names: HashMap<String, String>,
+}
+
+/// A reference seen from one end, with the issue or PR at the other.
+#[derive(Debug, Clone, Serialize)]
+pub struct Link {
+    pub node: Node,
+    pub kind: ReferenceKind,
+    pub comment_id: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct IssueReferences {
+    pub issue: Node,
+    /// Issues and PRs that reference this one.
+    pub referenced_by: Vec<Link>,
+    /// Issues and PRs this one references.
+    pub references: Vec<Link>,
+    /// Issues that can't be finished before this one, because they track it in a task
+    /// list or depend on it.
+    pub blocks: Vec<Link>,
+    /// Issues this one tracks or depends on.
+    pub blocked_by: Vec<Link>,
+    /// PRs that close this issue when merged.
+    pub closed_by: Vec<Link>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct GraphData {
+    pub nodes: Vec<Node>,
+    pub edges: Vec<Edge>,
+}
+
+/// Spells `repo` like the cache key of the same repo, if there is one.
+fn cached_name(repo_names: &HashMap<String, String>, repo: &str) -> String {
+    repo_names
+        .get(&repo.to_lowercase())
+        .cloned()
+        .unwrap_or_else(|| repo.to_string())
+}
+
+impl ReferenceGraph {
+    /// Builds the graph from the issue and PR caches. Repo names are matched without
+    /// regard to case, and use the spelling of the cache key.
+    pub fn build(
+        issues: &HashMap<String, CachedIssues>,
+        pulls: &HashMap<String, CachedPullRequests>,
+    ) -> Self {
+        let repo_names: HashMap<String, String> = issues
+            .keys()
+            .chain(pulls.keys())
+            .map(|key| (key.to_lowercase(), key.clone()))
+            .collect();
+        let resolve = |source: &str, reference: &IssueRef| IssueKey {
+            repo: match &reference.repo {
+                Some(repo) => cached_name(&repo_names, repo),
+                None => source.to_string(),
+            },
+            number: reference.number,
+        };
+
+        let mut graph = ReferenceGraph::default();
+        let mut seen: HashSet<(IssueKey, IssueKey, ReferenceKind)> = HashSet::new();
+        let mut add = |graph: &mut ReferenceGraph,
+                       from: &IssueKey,
+                       reference: &IssueRef,
+                       kind: ReferenceKind,
+                       comment_id: Option<i64>| {
+            let to = resolve(&from.repo, reference);
+            if to == *from || !seen.insert((from.clone(), to.clone(), kind)) {
+                return;
+            }
+            graph.edges.push(Edge {
+                from: from.clone(),
+                to,
+                kind,
+                comment_id,
+            });
+        };
+
+        for (repo, cached) in issues {
+            for issue in cached.issues.iter().filter(|issue| !issue.pending) {
+                let key = IssueKey {
+                    repo: repo.clone(),
+                    number: issue.number,
+                };
+                graph.nodes.insert(
+                    key.clone(),
+                    Node {
+                        repo: repo.clone(),
+                        number: issue.number,
+                        title: Some(issue.title.clone()),
+                        state: Some(issue.state.clone()),
+                        is_pull_request: issue.is_pull_request,
+                        cached: true,
+                    },
+                );
+
+                let tracked: HashSet<&IssueRef> = issue
+                    .tasks
+                    .items
+                    .iter()
+                    .flat_map(|item| &item.references)
+                    .collect();
+                for reference in parse_references(issue.body.as_deref().unwrap_or_default()) {
+                    let kind = match reference.kind {
+                        ReferenceKind::Mentions if tracked.contains(&reference.issue) => {
+                            ReferenceKind::Tracks
+                        }
+                        kind => kind,
+                    };
+                    add(&mut graph, &key, &reference.issue, kind, None);
+                }
+
+                for comment in issue.comments.iter().filter(|comment| !comment.pending) {
+                    for reference in parse_references(&comment.body) {
+                        add(
+                            &mut graph,
+                            &key,
+                            &reference.issue,
+                            reference.kind,
+                            Some(comment.id),
+                        );
+                    }
+                }
+            }
+        }
+
+        for (repo, cached) in pulls {
+            for pull in &cached.pulls {
+                let key = IssueKey {
+                    repo: repo.clone(),
+                    number: pull.number,
+                };
+                graph.nodes.insert(
+                    key.clone(),
+                    Node {
+                        repo: repo.clone(),
+                        number: pull.number,
+                        title: Some(pull.title.clone()),
+                        state: Some(pull.state.clone()),
+                        is_pull_request: true,
+                        cached: true,
+                    },
+                );
+
+                for reference in parse_references(pull.body.as_deref().unwrap_or_default()) {
+                    add(&mut graph, &key, &reference.issue, reference.kind, None);
+                }
+
+                for comment in &pull.comments {
+                    for reference in parse_references(&comment.body) {
+                        add(
+                            &mut graph,
+                            &key,
+                            &reference.issue,
+                            reference.kind,
+                            Some(comment.id),
+                        );
+                    }
+                }
+            }
+        }
+
+        graph.repo_names = repo_names;
+        graph
+    }
+
+    /// The key of issue `number` in `repo`, matching the repo without regard to case.
+    pub fn key(&self, repo: &str, number: i64) -> IssueKey {
+        IssueKey {
+            repo: cached_name(&self.repo_names, repo),
+            number,
+        }
+    }
+
+    fn node(&self, key: &IssueKey) -> Node {
+        self.nodes.get(key).cloned().unwrap_or_else(|| Node {
+            repo: key.repo.clone(),
+            number: key.number,
+            title: None,
+            state: None,
+            is_pull_request: false,
+            cached: false,
+        })
+    }
+
+    fn link(&self, edge: &Edge, other: &IssueKey) -> Link {
+        Link {
+            node: self.node(other),
+            kind: edge.kind,
+            comment_id: edge.comment_id,
+        }
+    }
+
+    pub fn references_of(&self, key: &IssueKey) -> IssueReferences {
+        let mut result = IssueReferences {
+            issue: self.node(key),
+            referenced_by: Vec::new(),
+            references: Vec::new(),
+            blocks: Vec::new(),
+            blocked_by: Vec::new(),
+            closed_by: Vec::new(),
+        };
+
+        let blocking = |kind| matches!(kind, ReferenceKind::Tracks | ReferenceKind::DependsOn);
+        for edge in &self.edges {
+            if edge.to == *key {
+                let link = self.link(edge, &edge.from);
+                if blocking(edge.kind) {
+                    result.blocks.push(link.clone());
+                }
+                // Only PRs close issues, but a source that isn't cached may well be one
+                if edge.kind == ReferenceKind::Closes
+                    && (link.node.is_pull_request || !link.node.cached)
+                {
+                    result.closed_by.push(link.clone());
+                }
+                result.referenced_by.push(link);
+            } else if edge.from == *key {
+                let link = self.link(edge, &edge.to);
+                if blocking(edge.kind) {
+                    result.blocked_by.push(link.clone());
+                }
+                result.references.push(link);
+            }
+        }
+
+        result
+    }
+
+    /// The part of the graph touching `repos`, or all of it.
+    pub fn data(self, repos: Option<&[String]>) -> GraphData {
+        let included = |key: &IssueKey| {
+            repos.is_none_or(|repos| {
+                repos
+                    .iter()
+                    .any(|repo| repo.eq_ignore_ascii_case(&key.repo))
+            })
+        };
+
+        let edges: Vec<Edge> = self
+            .edges
+            .iter()
+            .filter(|edge| included(&edge.from) || included(&edge.to))
+            .cloned()
+            .collect();
+        let mut keys: HashSet<&IssueKey> = edges
+            .iter()
+            .flat_map(|edge| [&edge.from, &edge.to])
+            .collect();
+        keys.extend(self.nodes.keys().filter(|key| included(key)));
+        let nodes = keys.into_iter().map(|key| self.node(key)).collect();
+
+        GraphData { nodes, edges }
+    }
+}
+
+fn build_graph(issues: &IssuesCache, pulls: &PullRequestsCache) -> Result<ReferenceGraph, String> {
+    let issues_guard = issues.get_cache().lock().map_err(|e| e.to_string())?;
+    let pulls_guard = pulls.get_cache().lock().map_err(|e| e.to_string())?;
+    Ok(ReferenceGraph::build(&issues_guard, &pulls_guard))
+}
+
+/// What references an issue, what it references, what it blocks and which PRs close it,
+/// across every cached repo.
+#[command]
+pub async fn get_issue_references(
+    repo: String,
+    number: i64,
+    issues: State<'_, IssuesCache>,
+    pulls: State<'_, PullRequestsCache>,
+) -> Result<IssueReferences, String> {
+    let graph = build_graph(&issues, &pulls)?;
+    Ok(graph.references_of(&graph.key(&repo, number)))
+}
+
+/// The cross-reference graph of the cached issues and PRs, limited to those in or
+/// referenced from `repos` when given.
+#[command]
+pub async fn get_reference_graph(
+    repos: Option<Vec<String>>,
+    issues: State<'_, IssuesCache>,
+    pulls: State<'_, PullRequestsCache>,
+) -> Result<GraphData, String> {
+    let graph = build_graph(&issues, &pulls)?;
+    Ok(graph.data(repos.as_deref()))
+}
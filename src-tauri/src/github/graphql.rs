@@ -0,0 +1,864 @@
+use super::issues::{CommentData, IssueData, IssueStateFilter, ReactionSummary};
+use super::milestones::{completion, MilestoneData};
+use super::oauth::get_token;
+use super::rate_limit::{
+    classify_status, record_with, with_rate_limit, with_retry, Classify, Failure, Resource,
+};
+use super::tasks::parse_tasks;
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::fmt;
+use tauri::AppHandle;
+
+const DEFAULT_GRAPHQL_URL: &str = "https://api.github.com/graphql";
+/// Issues per query. Kept low so the nested comment connections stay under GitHub's node limit.
+const ISSUES_PER_QUERY: usize = 50;
+/// Comments included with each issue; issues with more load the rest lazily over REST.
+const COMMENTS_PER_ISSUE: usize = 30;
+
+const ISSUES_QUERY: &str = r#"
+query($owner: String!, $name: String!, $first: Int!, $after: String, $states: [IssueState!], $since: DateTime, $comments: Int!) {
+  repository(owner: $owner, name: $name) {
+    issues(first: $first, after: $after, states: $states, filterBy: {since: $since}, orderBy: {field: UPDATED_AT, direction: DESC}) {
+      pageInfo { hasNextPage endCursor }
+      nodes {
+        number
+        title
+        state
+        stateReason
+        createdAt
+        updatedAt
+        body
+        author { login }
+        labels(first: 50) { nodes { name } }
+        assignees(first: 20) { nodes { login } }
+        milestone { number title description state dueOn }
+        reactionGroups { content reactors { totalCount } }
+        locked
+        activeLockReason
+        isPinned
+        comments(first: $comments) {
+          totalCount
+          nodes {
+            databaseId
+            fullDatabaseId
+            body
+            createdAt
+            lastEditedAt
+            author { login }
+            reactionGroups { content reactors { totalCount } }
+          }
+        }
+      }
+    }
+  }
+}
+"#;
+
+/// Issue counts of every milestone, loaded once per sync rather than for each issue.
+const MILESTONES_QUERY: &str = r#"
+query($owner: String!, $name: String!, $after: String) {
+  repository(owner: $owner, name: $name) {
+    milestones(first: 100, after: $after) {
+      pageInfo { hasNextPage endCursor }
+      nodes {
+        number
+        openIssues: issues(states: OPEN) { totalCount }
+        closedIssues: issues(states: CLOSED) { totalCount }
+      }
+    }
+  }
+}
+"#;
+
+/// Endpoint for GraphQL requests. `GITHUB_GRAPHQL_URL` points it at a local mock server.
+pub fn graphql_url() -> String {
+    std::env::var("GITHUB_GRAPHQL_URL").unwrap_or_else(|_| DEFAULT_GRAPHQL_URL.to_string())
+}
+
+pub struct GraphqlClient {
+    http: reqwest::Client,
+    url: String,
+    token: String,
+}
+
+#[derive(Deserialize)]
+struct GraphqlResponse<T> {
+    data: Option<T>,
+    #[serde(default)]
+    errors: Vec<GraphqlError>,
+}
+
+#[derive(Deserialize)]
+struct GraphqlError {
+    message: String,
+    #[serde(rename = "type")]
+    kind: Option<String>,
+}
+
+impl GraphqlClient {
+    pub fn new(url: String, token: String) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            url,
+            token,
+        }
+    }
+
+    pub fn from_app(app: &AppHandle) -> Result<Self, String> {
+        Ok(Self::new(graphql_url(), get_token(app)?))
+    }
+
+    /// Runs a query, waiting out rate limits and retrying transient failures.
+    pub async fn query<T: DeserializeOwned>(
+        &self,
+        query: &str,
+        variables: Value,
+    ) -> Result<T, String> {
+        let body = json!({ "query": query, "variables": variables });
+        with_retry(Resource::Graphql, || self.send(&body)).await
+    }
+
+    /// Like `query`, but GitHub refusing the request, like bad credentials or a missing
+    /// repo, comes back as `Ok(Err(..))` so callers can tell it from GitHub being unreachable.
+    pub async fn query_or_refused<T: DeserializeOwned>(
+        &self,
+        query: &str,
+        variables: Value,
+    ) -> Result<Result<T, String>, String> {
+        let body = json!({ "query": query, "variables": variables });
+        with_retry(Resource::Graphql, || async {
+            match self.send(&body).await {
+                Err(e) if e.classify() == Failure::Fatal => Ok(Err(e.message)),
+                result => result.map(Ok),
+            }
+        })
+        .await
+    }
+
+    /// Runs a mutation. Like other writes it waits out rate limits but isn't retried,
+    /// so it can't be applied twice.
+    pub async fn mutate<T: DeserializeOwned>(
+        &self,
+        mutation: &str,
+        variables: Value,
+    ) -> Result<T, String> {
+        let body = json!({ "query": mutation, "variables": variables });
+        with_rate_limit(Resource::Graphql, || self.send(&body)).await
+    }
+
+    async fn send<T: DeserializeOwned>(&self, body: &Value) -> Result<T, GraphqlFailure> {
+        let response = self
+            .http
+            .post(&self.url)
+            .bearer_auth(&self.token)
+            .header("User-Agent", "git-pulse")
+            .json(body)
+            .send()
+            .await
+            .map_err(|e| GraphqlFailure {
+                message: format!("GraphQL request failed: {}", e),
+                failure: Failure::Transient,
+            })?;
+
+        record_with(|name| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(String::from)
+        });
+
+        let status = response.status();
+        let text = response.text().await.map_err(|e| GraphqlFailure {
+            message: format!("Failed to read GraphQL response: {}", e),
+            failure: Failure::Transient,
+        })?;
+        if !status.is_success() {
+            return Err(GraphqlFailure {
+                message: format!("GraphQL request failed ({}): {}", status, text),
+                failure: classify_status(status.as_u16(), &text),
+            });
+        }
+
+        let body: GraphqlResponse<T> = serde_json::from_str(&text).map_err(|e| GraphqlFailure {
+            message: format!("Failed to parse GraphQL response: {}", e),
+            failure: Failure::Fatal,
+        })?;
+
+        if !body.errors.is_empty() {
+            // Running out of GraphQL points comes back as a 200 with a RATE_LIMITED error
+            let rate_limited = body
+                .errors
+                .iter()
+                .any(|e| e.kind.as_deref() == Some("RATE_LIMITED"));
+            let messages: Vec<String> = body.errors.into_iter().map(|e| e.message).collect();
+            return Err(GraphqlFailure {
+                message: format!("GraphQL error: {}", messages.join("; ")),
+                failure: if rate_limited {
+                    Failure::RateLimited { secondary: false }
+                } else {
+                    Failure::Fatal
+                },
+            });
+        }
+
+        body.data.ok_or_else(|| GraphqlFailure {
+            message: "GraphQL response had no data".to_string(),
+            failure: Failure::Fatal,
+        })
+    }
+}
+
+struct GraphqlFailure {
+    message: String,
+    failure: Failure,
+}
+
+impl Classify for GraphqlFailure {
+    fn classify(&self) -> Failure {
+        self.failure
+    }
+}
+
+impl fmt::Display for GraphqlFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+#[derive(Deserialize)]
+struct RepositoryData {
+    repository: Option<Repository>,
+}
+
+#[derive(Deserialize)]
+struct Repository {
+    issues: Connection<IssueNode>,
+}
+
+#[derive(Deserialize)]
+struct MilestonesData {
+    repository: Option<MilestonesRepository>,
+}
+
+#[derive(Deserialize)]
+struct MilestonesRepository {
+    milestones: Connection<MilestoneCounts>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct MilestoneCounts {
+    number: i64,
+    open_issues: TotalCount,
+    closed_issues: TotalCount,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Connection<T> {
+    page_info: Option<PageInfo>,
+    total_count: Option<i64>,
+    nodes: Vec<T>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PageInfo {
+    has_next_page: bool,
+    end_cursor: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Login {
+    login: String,
+}
+
+#[derive(Deserialize)]
+struct Name {
+    name: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TotalCount {
+    total_count: i64,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct IssueNode {
+    number: i64,
+    title: String,
+    state: String,
+    state_reason: Option<String>,
+    created_at: String,
+    updated_at: String,
+    body: Option<String>,
+    author: Option<Login>,
+    labels: Option<Connection<Name>>,
+    assignees: Connection<Login>,
+    milestone: Option<MilestoneNode>,
+    #[serde(default)]
+    reaction_groups: Vec<ReactionGroup>,
+    #[serde(default)]
+    locked: bool,
+    active_lock_reason: Option<String>,
+    is_pinned: Option<bool>,
+    comments: Connection<CommentNode>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct MilestoneNode {
+    number: i64,
+    title: String,
+    description: Option<String>,
+    state: String,
+    due_on: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CommentNode {
+    database_id: Option<i64>,
+    // Ids past the 32-bit range only come back as a string here
+    full_database_id: Option<String>,
+    body: String,
+    created_at: String,
+    last_edited_at: Option<String>,
+    author: Option<Login>,
+    #[serde(default)]
+    reaction_groups: Vec<ReactionGroup>,
+}
+
+#[derive(Deserialize)]
+struct ReactionGroup {
+    content: String,
+    reactors: TotalCount,
+}
+
+/// GraphQL timestamps use `Z` where the REST path's `to_rfc3339` writes `+00:00`.
+/// Normalising keeps `updated_at` comparable between the two sync engines.
+pub(crate) fn normalize_timestamp(value: &str) -> String {
+    DateTime::parse_from_rfc3339(value)
+        .map(|t| t.with_timezone(&Utc).to_rfc3339())
+        .unwrap_or_else(|_| value.to_string())
+}
+
+/// GraphQL spells lock reasons `OFF_TOPIC`, REST spells them `off-topic`.
+fn lock_reason(value: &str) -> String {
+    match value {
+        "OFF_TOPIC" => String::from("off-topic"),
+        "TOO_HEATED" => String::from("too heated"),
+        other => other.to_lowercase(),
+    }
+}
+
+fn reaction_summary(groups: &[ReactionGroup]) -> ReactionSummary {
+    let mut summary = ReactionSummary::default();
+    for group in groups {
+        let count = group.reactors.total_count;
+        match group.content.as_str() {
+            "THUMBS_UP" => summary.plus_one = count,
+            "THUMBS_DOWN" => summary.minus_one = count,
+            "LAUGH" => summary.laugh = count,
+            "HOORAY" => summary.hooray = count,
+            "CONFUSED" => summary.confused = count,
+            "HEART" => summary.heart = count,
+            "ROCKET" => summary.rocket = count,
+            "EYES" => summary.eyes = count,
+            _ => continue,
+        }
+        summary.total_count += count;
+    }
+    summary
+}
+
+// The issue counts are filled in afterwards by `add_milestone_counts`
+impl From<MilestoneNode> for MilestoneData {
+    fn from(milestone: MilestoneNode) -> Self {
+        MilestoneData {
+            number: milestone.number,
+            title: milestone.title,
+            description: milestone.description,
+            state: milestone.state.to_lowercase(),
+            due_on: milestone.due_on.as_deref().map(normalize_timestamp),
+            open_issues: 0,
+            closed_issues: 0,
+            completion: 0.0,
+        }
+    }
+}
+
+impl From<CommentNode> for CommentData {
+    fn from(comment: CommentNode) -> Self {
+        CommentData {
+            id: comment
+                .full_database_id
+                .and_then(|id| id.parse().ok())
+                .or(comment.database_id)
+                .unwrap_or_default(),
+            tasks: parse_tasks(Some(&comment.body)),
+            body: comment.body,
+            created_at: normalize_timestamp(&comment.created_at),
+            updated_at: comment.last_edited_at.as_deref().map(normalize_timestamp),
+            author: comment
+                .author
+                .map(|author| author.login)
+                .unwrap_or_else(|| String::from("ghost")),
+            reactions: Some(reaction_summary(&comment.reaction_groups)),
+            pending: false,
+        }
+    }
+}
+
+impl From<IssueNode> for IssueData {
+    fn from(issue: IssueNode) -> Self {
+        let comments_count = issue.comments.total_count.unwrap_or_default();
+        let comments: Vec<CommentData> = issue
+            .comments
+            .nodes
+            .into_iter()
+            .map(CommentData::from)
+            .collect();
+
+        IssueData {
+            number: issue.number,
+            title: issue.title,
+            state: issue.state.to_lowercase(),
+            state_reason: issue.state_reason.map(|reason| reason.to_lowercase()),
+            created_at: normalize_timestamp(&issue.created_at),
+            updated_at: normalize_timestamp(&issue.updated_at),
+            tasks: parse_tasks(issue.body.as_deref()),
+            body: issue.body,
+            labels: issue
+                .labels
+                .map(|labels| labels.nodes.into_iter().map(|label| label.name).collect())
+                .unwrap_or_default(),
+            assignees: issue
+                .assignees
+                .nodes
+                .into_iter()
+                .map(|user| user.login)
+                .collect(),
+            milestone: issue.milestone.map(MilestoneData::from),
+            comments_loaded: comments.len() as i64 >= comments_count,
+            comments,
+            comments_count,
+            comments_error: None,
+            creator: issue
+                .author
+                .map(|author| author.login)
+                .unwrap_or_else(|| String::from("ghost")),
+            // The issues connection never includes pull requests
+            is_pull_request: false,
+            reactions: Some(reaction_summary(&issue.reaction_groups)),
+            pending: false,
+            locked: issue.locked,
+            lock_reason: issue.active_lock_reason.as_deref().map(lock_reason),
+            pinned: issue.is_pinned,
+        }
+    }
+}
+
+/// Lists a repo's issues with their first comments, labels, assignees, milestone and
+/// reactions, a page of issues per request. Returns the issues and whether the cap was hit,
+/// or `Ok(Err(..))` when GitHub refused the listing rather than couldn't be reached.
+pub async fn fetch_issues(
+    client: &GraphqlClient,
+    owner: &str,
+    repo: &str,
+    state: IssueStateFilter,
+    since: Option<DateTime<Utc>>,
+    max_issues: usize,
+) -> Result<Result<(Vec<IssueData>, bool), String>, String> {
+    // Like the REST engine, an incremental sync asks for every state so issues that were
+    // closed or reopened since move over
+    let states = match state {
+        _ if since.is_some() => Value::Null,
+        IssueStateFilter::Open => json!(["OPEN"]),
+        IssueStateFilter::Closed => json!(["CLOSED"]),
+        IssueStateFilter::All => Value::Null,
+    };
+    let since = since.map(|since| since.to_rfc3339_opts(SecondsFormat::Secs, true));
+
+    let mut issues: Vec<IssueData> = Vec::new();
+    let mut after: Option<String> = None;
+    loop {
+        let data: RepositoryData = match client
+            .query_or_refused(
+                ISSUES_QUERY,
+                json!({
+                    "owner": owner,
+                    "name": repo,
+                    "first": ISSUES_PER_QUERY,
+                    "after": after,
+                    "states": states,
+                    "since": since,
+                    "comments": COMMENTS_PER_ISSUE,
+                }),
+            )
+            .await?
+        {
+            Ok(data) => data,
+            Err(e) => return Ok(Err(e)),
+        };
+        let Some(repository) = data.repository else {
+            return Ok(Err(format!("Repository {}/{} not found", owner, repo)));
+        };
+        let connection = repository.issues;
+
+        issues.extend(connection.nodes.into_iter().map(IssueData::from));
+
+        let page_info = connection.page_info;
+        let has_next_page = page_info.as_ref().is_some_and(|info| info.has_next_page);
+        if issues.len() >= max_issues {
+            let truncated = issues.len() > max_issues || has_next_page;
+            issues.truncate(max_issues);
+            add_milestone_counts(client, owner, repo, &mut issues).await?;
+            return Ok(Ok((issues, truncated)));
+        }
+        if !has_next_page {
+            add_milestone_counts(client, owner, repo, &mut issues).await?;
+            return Ok(Ok((issues, false)));
+        }
+        after = page_info.and_then(|info| info.end_cursor);
+    }
+}
+
+/// Fills in the open and closed issue counts of the issues' milestones.
+async fn add_milestone_counts(
+    client: &GraphqlClient,
+    owner: &str,
+    repo: &str,
+    issues: &mut [IssueData],
+) -> Result<(), String> {
+    if issues.iter().all(|issue| issue.milestone.is_none()) {
+        return Ok(());
+    }
+
+    let mut counts: HashMap<i64, (i64, i64)> = HashMap::new();
+    let mut after: Option<String> = None;
+    loop {
+        let data: MilestonesData = client
+            .query(
+                MILESTONES_QUERY,
+                json!({ "owner": owner, "name": repo, "after": after }),
+            )
+            .await?;
+        let connection = data
+            .repository
+            .ok_or_else(|| format!("Repository {}/{} not found", owner, repo))?
+            .milestones;
+
+        for milestone in connection.nodes {
+            counts.insert(
+                milestone.number,
+                (
+                    milestone.open_issues.total_count,
+                    milestone.closed_issues.total_count,
+                ),
+            );
+        }
+
+        match connection.page_info {
+            Some(info) if info.has_next_page => after = info.end_cursor,
+            _ => break,
+        }
+    }
+
+    for milestone in issues
+        .iter_mut()
+        .filter_map(|issue| issue.milestone.as_mut())
+    {
+        if let Some(&(open_issues, closed_issues)) = counts.get(&milestone.number) {
+            milestone.open_issues = open_issues;
+            milestone.closed_issues = closed_issues;
+            milestone.completion = completion(open_issues, closed_issues);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
+
+    type Requests = Arc<Mutex<Vec<Value>>>;
+
+    /// A local GraphQL endpoint answering every request with `respond`. Keeps the request
+    /// bodies so tests can check the variables that were sent.
+    async fn stub_server(respond: fn(&Value) -> Value) -> (GraphqlClient, Requests) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/graphql", listener.local_addr().unwrap());
+        let requests = Requests::default();
+
+        let seen = requests.clone();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let request = read_request(&mut socket).await;
+                let body = respond(&request).to_string();
+                seen.lock().unwrap().push(request);
+
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                );
+                socket.write_all(response.as_bytes()).await.unwrap();
+                socket.shutdown().await.ok();
+            }
+        });
+
+        (GraphqlClient::new(url, String::from("token")), requests)
+    }
+
+    async fn read_request(socket: &mut TcpStream) -> Value {
+        let mut buffer = Vec::new();
+        let mut chunk = [0; 4096];
+        loop {
+            let read = socket.read(&mut chunk).await.unwrap();
+            assert!(read > 0, "connection closed mid-request");
+            buffer.extend_from_slice(&chunk[..read]);
+
+            let text = String::from_utf8_lossy(&buffer);
+            let Some(end) = text.find("\r\n\r\n") else {
+                continue;
+            };
+            let length = text[..end]
+                .lines()
+                .find_map(|line| {
+                    let (name, value) = line.split_once(':')?;
+                    name.eq_ignore_ascii_case("content-length")
+                        .then(|| value.trim().parse::<usize>().ok())?
+                })
+                .unwrap_or(0);
+            if buffer.len() >= end + 4 + length {
+                return serde_json::from_slice(&buffer[end + 4..end + 4 + length]).unwrap();
+            }
+        }
+    }
+
+    fn issue(number: i64) -> Value {
+        json!({
+            "number": number,
+            "title": format!("Issue {}", number),
+            "state": "OPEN",
+            "stateReason": null,
+            "createdAt": "2024-01-01T00:00:00Z",
+            "updatedAt": format!("2024-02-0{}T00:00:00Z", number),
+            "body": null,
+            "author": { "login": "monalisa" },
+            "labels": { "nodes": [] },
+            "assignees": { "nodes": [] },
+            "milestone": null,
+            "reactionGroups": [],
+            "locked": false,
+            "activeLockReason": null,
+            "isPinned": false,
+            "comments": { "totalCount": 0, "nodes": [] }
+        })
+    }
+
+    fn detailed_issue() -> Value {
+        let mut issue = issue(3);
+        let fields = json!({
+            "state": "CLOSED",
+            "stateReason": "NOT_PLANNED",
+            "createdAt": "2024-01-02T03:04:05Z",
+            "body": "- [x] Reproduce\n- [ ] Fix #2",
+            "labels": { "nodes": [{ "name": "bug" }] },
+            "assignees": { "nodes": [{ "login": "octocat" }] },
+            "milestone": {
+                "number": 1,
+                "title": "v1.0",
+                "description": null,
+                "state": "OPEN",
+                "dueOn": null
+            },
+            "reactionGroups": [
+                { "content": "THUMBS_UP", "reactors": { "totalCount": 2 } },
+                { "content": "HEART", "reactors": { "totalCount": 1 } }
+            ],
+            "locked": true,
+            "activeLockReason": "OFF_TOPIC",
+            "isPinned": true,
+            "comments": {
+                "totalCount": 2,
+                "nodes": [{
+                    "databaseId": null,
+                    "fullDatabaseId": "9000000000",
+                    "body": "Seen it too",
+                    "createdAt": "2024-01-03T00:00:00Z",
+                    "lastEditedAt": null,
+                    "author": null,
+                    "reactionGroups": []
+                }]
+            }
+        });
+        for (key, value) in fields.as_object().unwrap() {
+            issue[key] = value.clone();
+        }
+        issue
+    }
+
+    /// Two pages of issues, newest first, and the counts of milestone 1.
+    fn two_pages(request: &Value) -> Value {
+        if request["query"].as_str().unwrap().contains("milestones(") {
+            return json!({ "data": { "repository": { "milestones": {
+                "pageInfo": { "hasNextPage": false, "endCursor": null },
+                "nodes": [{
+                    "number": 1,
+                    "openIssues": { "totalCount": 1 },
+                    "closedIssues": { "totalCount": 3 }
+                }]
+            }}}});
+        }
+
+        let (nodes, has_next_page) = match request["variables"]["after"].as_str() {
+            None => (vec![detailed_issue(), issue(2)], true),
+            Some("page-2") => (vec![issue(1)], false),
+            Some(other) => panic!("unexpected cursor {}", other),
+        };
+        json!({ "data": { "repository": { "issues": {
+            "pageInfo": { "hasNextPage": has_next_page, "endCursor": "page-2" },
+            "nodes": nodes
+        }}}})
+    }
+
+    #[tokio::test]
+    async fn follows_every_page() {
+        let (client, requests) = stub_server(two_pages).await;
+        let (issues, truncated) =
+            fetch_issues(&client, "owner", "repo", IssueStateFilter::Open, None, 10)
+                .await
+                .unwrap()
+                .unwrap();
+
+        assert!(!truncated);
+        let numbers: Vec<i64> = issues.iter().map(|issue| issue.number).collect();
+        assert_eq!(numbers, vec![3, 2, 1]);
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 3);
+        assert_eq!(requests[0]["variables"]["states"], json!(["OPEN"]));
+        assert_eq!(requests[1]["variables"]["after"], json!("page-2"));
+    }
+
+    #[tokio::test]
+    async fn stops_at_the_cap_and_reports_truncation() {
+        let (client, requests) = stub_server(two_pages).await;
+        let (issues, truncated) =
+            fetch_issues(&client, "owner", "repo", IssueStateFilter::All, None, 2)
+                .await
+                .unwrap()
+                .unwrap();
+
+        assert!(truncated);
+        assert_eq!(issues.len(), 2);
+        // One page of issues, then the milestone counts
+        assert_eq!(requests.lock().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn incremental_syncs_ask_for_every_state() {
+        let (client, requests) = stub_server(two_pages).await;
+        let since = DateTime::parse_from_rfc3339("2024-01-15T00:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        fetch_issues(
+            &client,
+            "owner",
+            "repo",
+            IssueStateFilter::Open,
+            Some(since),
+            10,
+        )
+        .await
+        .unwrap()
+        .unwrap();
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests[0]["variables"]["states"], Value::Null);
+        assert_eq!(
+            requests[0]["variables"]["since"],
+            json!("2024-01-15T00:00:00Z")
+        );
+    }
+
+    #[tokio::test]
+    async fn maps_issues_and_comments() {
+        let (client, _) = stub_server(two_pages).await;
+        let (issues, _) = fetch_issues(&client, "owner", "repo", IssueStateFilter::All, None, 10)
+            .await
+            .unwrap()
+            .unwrap();
+        let issue = &issues[0];
+
+        assert_eq!(issue.title, "Issue 3");
+        assert_eq!(issue.state, "closed");
+        assert_eq!(issue.state_reason.as_deref(), Some("not_planned"));
+        assert_eq!(issue.created_at, "2024-01-02T03:04:05+00:00");
+        assert_eq!(issue.labels, vec!["bug"]);
+        assert_eq!(issue.assignees, vec!["octocat"]);
+        assert_eq!(issue.creator, "monalisa");
+        assert!(!issue.is_pull_request);
+        assert!(issue.locked);
+        assert_eq!(issue.lock_reason.as_deref(), Some("off-topic"));
+        assert_eq!(issue.pinned, Some(true));
+        assert_eq!((issue.tasks.total, issue.tasks.completed), (2, 1));
+
+        let milestone = issue.milestone.as_ref().unwrap();
+        assert_eq!(milestone.title, "v1.0");
+        assert_eq!(milestone.state, "open");
+        assert_eq!((milestone.open_issues, milestone.closed_issues), (1, 3));
+        assert_eq!(milestone.completion, 75.0);
+
+        let reactions = issue.reactions.as_ref().unwrap();
+        assert_eq!((reactions.plus_one, reactions.heart), (2, 1));
+        assert_eq!(reactions.total_count, 3);
+
+        // Only one of the two comments came with the issue
+        assert_eq!(issue.comments_count, 2);
+        assert!(!issue.comments_loaded);
+        let comment = &issue.comments[0];
+        assert_eq!(comment.id, 9_000_000_000);
+        assert_eq!(comment.author, "ghost");
+        assert_eq!(comment.body, "Seen it too");
+        assert_eq!(comment.created_at, "2024-01-03T00:00:00+00:00");
+        assert_eq!(comment.updated_at, None);
+
+        assert!(issues[1].comments_loaded);
+        assert!(issues[1].milestone.is_none());
+    }
+
+    #[tokio::test]
+    async fn errors_in_the_response_are_refusals() {
+        let (client, _) =
+            stub_server(|_| json!({ "errors": [{ "message": "Bad credentials" }] })).await;
+        let error = fetch_issues(&client, "owner", "repo", IssueStateFilter::All, None, 10)
+            .await
+            .unwrap()
+            .unwrap_err();
+        assert!(error.contains("Bad credentials"));
+    }
+
+    #[tokio::test]
+    async fn a_missing_repo_is_a_refusal() {
+        let (client, _) = stub_server(|_| json!({ "data": { "repository": null } })).await;
+        let error = fetch_issues(&client, "owner", "repo", IssueStateFilter::All, None, 10)
+            .await
+            .unwrap()
+            .unwrap_err();
+        assert_eq!(error, "Repository owner/repo not found");
+    }
+}
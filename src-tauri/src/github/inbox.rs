@@ -0,0 +1,230 @@
+use super::github_client::get_client;
+use super::issues::{IssueData, RestIssue};
+use super::oauth::get_username;
+use super::rate_limit::{with_retry, Resource};
+use chrono::{DateTime, Duration, Utc};
+use octocrab::{Octocrab, Page};
+use serde::Serialize;
+use std::sync::Mutex;
+use std::{collections::HashMap, sync::Arc};
+use tauri::{command, AppHandle, State};
+
+/// Results kept per search. GitHub stops at 1000 and the search quota is only 30 requests a minute.
+const MAX_RESULTS_PER_REASON: usize = 300;
+
+#[derive(Debug, Default)]
+pub struct InboxCache {
+    // Keyed by login so switching accounts doesn't show someone else's inbox
+    cache: Arc<Mutex<HashMap<String, CachedInbox>>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct CachedInbox {
+    pub inbox: Inbox,
+    pub last_updated: DateTime<Utc>,
+}
+
+/// Why an issue or pull request is in the inbox, in the order the groups are shown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InboxReason {
+    ReviewRequested,
+    Assigned,
+    Mentioned,
+    Authored,
+}
+
+impl InboxReason {
+    const ALL: [InboxReason; 4] = [
+        InboxReason::ReviewRequested,
+        InboxReason::Assigned,
+        InboxReason::Mentioned,
+        InboxReason::Authored,
+    ];
+
+    fn query(self, login: &str) -> String {
+        match self {
+            InboxReason::ReviewRequested => {
+                format!("is:open is:pr archived:false review-requested:{}", login)
+            }
+            InboxReason::Assigned => format!("is:open archived:false assignee:{}", login),
+            InboxReason::Mentioned => format!("is:open archived:false mentions:{}", login),
+            InboxReason::Authored => format!("is:open archived:false author:{}", login),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct InboxItem {
+    pub repo: String,
+    #[serde(flatten)]
+    pub issue: IssueData,
+    /// Every reason that matched, the first one decides the group.
+    pub reasons: Vec<InboxReason>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct InboxGroup {
+    pub reason: InboxReason,
+    pub items: Vec<InboxItem>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Inbox {
+    pub groups: Vec<InboxGroup>,
+    pub last_updated: String,
+    /// Searches that failed, the groups they feed may be incomplete.
+    pub errors: Vec<String>,
+}
+
+/// `owner/name` from a search result's `repository_url`.
+fn repo_name(issue: &RestIssue) -> String {
+    let segments: Vec<&str> = issue
+        .issue
+        .repository_url
+        .path_segments()
+        .map(|segments| segments.collect())
+        .unwrap_or_default();
+
+    match segments.as_slice() {
+        [.., owner, name] => format!("{}/{}", owner, name),
+        _ => String::new(),
+    }
+}
+
+async fn search(octocrab: &Octocrab, query: &str) -> Result<Vec<RestIssue>, String> {
+    let mut page: Page<RestIssue> = with_retry(Resource::Search, || {
+        octocrab.get(
+            "/search/issues",
+            Some(&[("q", query), ("sort", "updated"), ("per_page", "100")]),
+        )
+    })
+    .await?;
+
+    let mut results = page.take_items();
+    while results.len() < MAX_RESULTS_PER_REASON {
+        match with_retry(Resource::Search, || {
+            octocrab.get_page::<RestIssue>(&page.next)
+        })
+        .await?
+        {
+            Some(mut next) => {
+                results.extend(next.take_items());
+                page = next;
+            }
+            None => break,
+        }
+    }
+    results.truncate(MAX_RESULTS_PER_REASON);
+
+    Ok(results)
+}
+
+/// Merges the per-reason results so each issue appears once, under its most pressing reason.
+fn group(results: Vec<(InboxReason, Vec<RestIssue>)>) -> Vec<InboxGroup> {
+    let mut items: HashMap<(String, i64), InboxItem> = HashMap::new();
+    for (reason, issues) in results {
+        for issue in issues {
+            let repo = repo_name(&issue);
+            let item = items
+                .entry((repo.clone(), issue.issue.number as i64))
+                .or_insert_with(|| InboxItem {
+                    repo,
+                    issue: IssueData::from(issue),
+                    reasons: Vec::new(),
+                });
+            if !item.reasons.contains(&reason) {
+                item.reasons.push(reason);
+            }
+        }
+    }
+
+    let mut groups: Vec<InboxGroup> = InboxReason::ALL
+        .iter()
+        .map(|reason| InboxGroup {
+            reason: *reason,
+            items: Vec::new(),
+        })
+        .collect();
+    for mut item in items.into_values() {
+        item.reasons.sort();
+        if let Some(group) = groups
+            .iter_mut()
+            .find(|group| group.reason == item.reasons[0])
+        {
+            group.items.push(item);
+        }
+    }
+    for group in &mut groups {
+        group
+            .items
+            .sort_by(|a, b| b.issue.updated_at.cmp(&a.issue.updated_at));
+    }
+
+    groups
+}
+
+/// Open issues and pull requests across every repo that are waiting on the signed-in user.
+#[command]
+pub async fn fetch_inbox(
+    app: AppHandle,
+    force_refresh: Option<bool>,
+    cache: State<'_, InboxCache>,
+) -> Result<Inbox, String> {
+    let login = get_username(app)?;
+
+    let cached = {
+        let cache_guard = cache.cache.lock().map_err(|e| e.to_string())?;
+        cache_guard.get(&login).cloned()
+    };
+    if let Some(cached) = &cached {
+        if !force_refresh.unwrap_or(false)
+            && Utc::now() - cached.last_updated < Duration::minutes(5)
+        {
+            println!("Returning cached inbox");
+            return Ok(cached.inbox.clone());
+        }
+    }
+
+    let octocrab = get_client()?;
+    let mut results = Vec::new();
+    let mut errors = Vec::new();
+    for reason in InboxReason::ALL {
+        match search(&octocrab, &reason.query(&login)).await {
+            Ok(issues) => results.push((reason, issues)),
+            Err(e) => {
+                println!("Inbox search for {:?} failed: {}", reason, e);
+                errors.push(e);
+            }
+        }
+    }
+
+    if results.is_empty() {
+        // Nothing came back, so the last inbox is better than an empty one
+        return match cached {
+            Some(cached) => Ok(cached.inbox),
+            None => Err(errors.join("; ")),
+        };
+    }
+
+    let now = Utc::now();
+    let inbox = Inbox {
+        groups: group(results),
+        last_updated: now.to_rfc3339(),
+        errors,
+    };
+
+    // A partial inbox is shown but not cached, so the next call tries again
+    if inbox.errors.is_empty() {
+        let mut cache_guard = cache.cache.lock().map_err(|e| e.to_string())?;
+        cache_guard.insert(
+            login,
+            CachedInbox {
+                inbox: inbox.clone(),
+                last_updated: now,
+            },
+        );
+    }
+
+    Ok(inbox)
+}
@@ -1,8 +1,16 @@
-use super::issues::{CommentData, IssueData, IssuesCache};
+use super::comments::load_comments;
+use super::graphql::GraphqlClient;
+use super::issues::{IssueData, IssuesCache, RestIssue};
 use super::oauth::get_token;
+use super::rate_limit::{with_rate_limit, with_retry, Classify, Failure, Resource};
+use crate::drafts::drafts::{clear_draft, DraftTarget};
 use crate::github::get_username;
-use chrono::Utc;
-use tauri::{command, AppHandle, State};
+use crate::outbox::queue::{hide_pending, show_pending, write_or_queue, Mutation, Outbox, Written};
+use octocrab::models::issues::{Issue, IssueStateReason};
+use octocrab::models::IssueState;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tauri::{command, AppHandle, Manager, State};
 
 #[command]
 pub async fn add_issue_comment(
@@ -20,15 +28,40 @@ pub async fn add_issue_comment(
         .build()
         .map_err(|e| e.to_string())?;
 
-    octocrab
-        .issues(&owner, &repo)
-        .create_comment(issue_number as u64, body)
-        .await
-        .map_err(|e| e.to_string())?;
+    let cache_key = format!("{}/{}", owner, repo);
+    let mutation = Mutation::AddComment {
+        owner: owner.clone(),
+        repo: repo.clone(),
+        issue_number,
+        body: body.clone(),
+        issue_state: cached_issue(&cache, &cache_key, issue_number)?.map(|issue| issue.state),
+    };
+
+    let written = write_or_queue(&app.state::<Outbox>(), mutation, || {
+        with_rate_limit(Resource::Core, || async {
+            octocrab
+                .issues(&owner, &repo)
+                .create_comment(issue_number as u64, &body)
+                .await
+        })
+    })
+    .await?;
+    clear_draft(&app, &cache_key, DraftTarget::Comment { issue_number });
+    if let Written::Queued(entry) = written {
+        return show_pending(&cache, &entry, &get_username(app.clone())?);
+    }
 
     fetch_single_issue(app, owner, repo, issue_number, cache).await
 }
 
+fn cached_issue(cache: &IssuesCache, key: &str, number: i64) -> Result<Option<IssueData>, String> {
+    let cache_guard = cache.get_cache().lock().map_err(|e| e.to_string())?;
+    Ok(cache_guard
+        .get(key)
+        .and_then(|cached| cached.issues.iter().find(|i| i.number == number))
+        .cloned())
+}
+
 #[command]
 pub async fn fetch_single_issue(
     app: AppHandle,
@@ -44,47 +77,60 @@ pub async fn fetch_single_issue(
         .build()
         .map_err(|e| e.to_string())?;
 
-    let issue = octocrab
-        .issues(&owner, &repo)
-        .get(issue_number as u64)
-        .await
-        .map_err(|e| e.to_string())?;
-
-    let mut issue_data = IssueData::from(issue);
-
-    let comments = octocrab
-        .issues(&owner, &repo)
-        .list_comments(issue_number as u64)
-        .send()
-        .await
-        .map_err(|e| e.to_string())?;
+    let cache_key = format!("{}/{}", owner, repo);
 
-    issue_data.comments = comments
-        .items
-        .into_iter()
-        .map(|comment| CommentData {
-            id: comment.id.0.try_into().unwrap_or_default(),
-            body: comment.body.unwrap_or_default(),
-            created_at: comment.created_at.to_rfc3339(),
-            updated_at: comment.updated_at.map(|t| t.to_rfc3339()),
-            author: comment.user.login,
-        })
-        .collect();
+    let route = format!("/repos/{}/{}/issues/{}", owner, repo, issue_number);
+    // GitHub refusing the request is passed through as a value, only failures to reach it
+    // are retried
+    let fetched = with_retry(Resource::Core, || async {
+        match octocrab.get::<RestIssue, _, _>(&route, None::<&()>).await {
+            Err(e) if e.classify() == Failure::Fatal => Ok(Err(e)),
+            result => result.map(Ok),
+        }
+    })
+    .await;
+    let issue = match fetched {
+        Ok(Ok(issue)) => issue,
+        Ok(Err(e)) => {
+            // Deleted or transferred away, so the cached copy is stale for good
+            if let octocrab::Error::GitHub { source, .. } = &e {
+                if matches!(source.status_code.as_u16(), 404 | 410) {
+                    cache.remove_issue(&cache_key, issue_number)?;
+                }
+            }
+            return Err(e.to_string());
+        }
+        Err(e) => {
+            // Fall back to the stored copy when GitHub can't be reached
+            return cached_issue(&cache, &cache_key, issue_number)?.ok_or(e);
+        }
+    };
 
-    // Update cache with new issue data
-    let cache_key = format!("{}/{}", owner, repo);
-    let mut cache_guard = cache.get_cache().lock().map_err(|e| e.to_string())?;
+    let mut issue_data = IssueData::from(issue);
 
-    if let Some((cached_issues, _last_updated)) = cache_guard.get_mut(&cache_key) {
-        if let Some(existing_issue) = cached_issues.iter_mut().find(|i| i.number == issue_number) {
-            *existing_issue = issue_data.clone();
-        } else {
-            cached_issues.push(issue_data.clone());
+    match load_comments(&octocrab, &owner, &repo, issue_number).await {
+        Ok(comments) => {
+            issue_data.comments_count = comments.len() as i64;
+            issue_data.comments = comments;
+            issue_data.comments_loaded = true;
+        }
+        Err(e) => {
+            println!("Error fetching comments for issue #{}: {}", issue_number, e);
+            // Keep the comments we already have rather than dropping them
+            let cache_guard = cache.get_cache().lock().map_err(|e| e.to_string())?;
+            if let Some(known) = cache_guard
+                .get(&cache_key)
+                .and_then(|cached| cached.issues.iter().find(|i| i.number == issue_number))
+            {
+                issue_data.comments = known.comments.clone();
+            }
+            issue_data.comments_error = Some(e);
         }
-    } else {
-        cache_guard.insert(cache_key, (vec![issue_data.clone()], Utc::now()));
     }
 
+    // Update cache with new issue data
+    cache.upsert_issue(&cache_key, issue_data.clone())?;
+
     Ok(issue_data)
 }
 
@@ -99,16 +145,34 @@ pub async fn delete_issue_comment(
     let token = get_token(&app)?;
     let owner = get_username(app.clone())?;
 
+    // A comment that was never sent only has to come out of the outbox
+    if comment_number < 0 {
+        let outbox = app.state::<Outbox>();
+        let queued = outbox
+            .entries()?
+            .into_iter()
+            .find(|entry| entry.placeholder_id() == comment_number);
+        if let Some(entry) = queued {
+            outbox.remove(entry.id)?;
+            hide_pending(&cache, &entry)?;
+        }
+        let cache_key = format!("{}/{}", owner, repo);
+        return cached_issue(&cache, &cache_key, issue_number)?
+            .ok_or_else(|| format!("Issue #{} is not cached", issue_number));
+    }
+
     let octocrab = octocrab::OctocrabBuilder::new()
         .personal_token(token)
         .build()
         .map_err(|e| e.to_string())?;
 
-    octocrab
-        .issues(&owner, &repo)
-        .delete_comment(octocrab::models::CommentId(comment_number as u64))
-        .await
-        .map_err(|e| e.to_string())?;
+    with_rate_limit(Resource::Core, || async {
+        octocrab
+            .issues(&owner, &repo)
+            .delete_comment(octocrab::models::CommentId(comment_number as u64))
+            .await
+    })
+    .await?;
 
     println!("Comment deleted");
     fetch_single_issue(app, owner.clone(), repo, issue_number, cache).await
@@ -125,17 +189,553 @@ pub async fn edit_issue_comment(
 ) -> Result<IssueData, String> {
     let token = get_token(&app)?;
     let owner = get_username(app.clone())?;
+    let outbox = app.state::<Outbox>();
+
+    // Editing a comment that was never sent changes what will be sent
+    if comment_number < 0 {
+        let entry = outbox
+            .rewrite_comment(comment_number, body)?
+            .ok_or_else(|| String::from("The comment is no longer in the outbox"))?;
+        return show_pending(&cache, &entry, &owner);
+    }
 
     let octocrab = octocrab::OctocrabBuilder::new()
         .personal_token(token)
         .build()
         .map_err(|e| e.to_string())?;
 
-    octocrab
-        .issues(&owner, &repo)
-        .update_comment(octocrab::models::CommentId(comment_number as u64), body)
-        .await
-        .map_err(|e| e.to_string())?;
+    let cache_key = format!("{}/{}", owner, repo);
+    let base_body = cached_issue(&cache, &cache_key, issue_number)?.and_then(|issue| {
+        issue
+            .comments
+            .into_iter()
+            .find(|c| c.id == comment_number)
+            .map(|c| c.body)
+    });
+    let mutation = Mutation::EditComment {
+        owner: owner.clone(),
+        repo: repo.clone(),
+        issue_number,
+        comment_id: comment_number,
+        body: body.clone(),
+        base_body,
+    };
+
+    let written = write_or_queue(&outbox, mutation, || {
+        with_rate_limit(Resource::Core, || async {
+            octocrab
+                .issues(&owner, &repo)
+                .update_comment(octocrab::models::CommentId(comment_number as u64), &body)
+                .await
+        })
+    })
+    .await?;
+    clear_draft(
+        &app,
+        &cache_key,
+        DraftTarget::CommentEdit {
+            issue_number,
+            comment_id: comment_number,
+        },
+    );
+    if let Written::Queued(entry) = written {
+        return show_pending(&cache, &entry, &owner);
+    }
 
     fetch_single_issue(app, owner.clone(), repo, issue_number, cache).await
 }
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CloseReason {
+    Completed,
+    NotPlanned,
+}
+
+fn build_client(app: &AppHandle) -> Result<octocrab::Octocrab, String> {
+    let token = get_token(app)?;
+
+    octocrab::OctocrabBuilder::new()
+        .personal_token(token)
+        .build()
+        .map_err(|e| e.to_string())
+}
+
+#[command]
+pub async fn update_issue(
+    app: AppHandle,
+    owner: String,
+    repo: String,
+    issue_number: i64,
+    title: Option<String>,
+    body: Option<String>,
+    cache: State<'_, IssuesCache>,
+) -> Result<IssueData, String> {
+    let octocrab = build_client(&app)?;
+
+    with_rate_limit(Resource::Core, || async {
+        let issues = octocrab.issues(&owner, &repo);
+        let mut update = issues.update(issue_number as u64);
+        if let Some(title) = &title {
+            update = update.title(title);
+        }
+        if let Some(body) = &body {
+            update = update.body(body);
+        }
+        update.send().await
+    })
+    .await?;
+
+    fetch_single_issue(app, owner, repo, issue_number, cache).await
+}
+
+#[command]
+pub async fn close_issue(
+    app: AppHandle,
+    owner: String,
+    repo: String,
+    issue_number: i64,
+    reason: CloseReason,
+    cache: State<'_, IssuesCache>,
+) -> Result<IssueData, String> {
+    let octocrab = build_client(&app)?;
+
+    with_rate_limit(Resource::Core, || async {
+        let state_reason = match reason {
+            CloseReason::Completed => IssueStateReason::Completed,
+            CloseReason::NotPlanned => IssueStateReason::NotPlanned,
+        };
+
+        octocrab
+            .issues(&owner, &repo)
+            .update(issue_number as u64)
+            .state(IssueState::Closed)
+            .state_reason(state_reason)
+            .send()
+            .await
+    })
+    .await?;
+
+    fetch_single_issue(app, owner, repo, issue_number, cache).await
+}
+
+#[command]
+pub async fn reopen_issue(
+    app: AppHandle,
+    owner: String,
+    repo: String,
+    issue_number: i64,
+    cache: State<'_, IssuesCache>,
+) -> Result<IssueData, String> {
+    let octocrab = build_client(&app)?;
+
+    with_rate_limit(Resource::Core, || async {
+        octocrab
+            .issues(&owner, &repo)
+            .update(issue_number as u64)
+            .state(IssueState::Open)
+            .send()
+            .await
+    })
+    .await?;
+
+    fetch_single_issue(app, owner, repo, issue_number, cache).await
+}
+
+#[command]
+pub async fn add_issue_labels(
+    app: AppHandle,
+    owner: String,
+    repo: String,
+    issue_number: i64,
+    labels: Vec<String>,
+    cache: State<'_, IssuesCache>,
+) -> Result<IssueData, String> {
+    let octocrab = build_client(&app)?;
+
+    with_rate_limit(Resource::Core, || async {
+        octocrab
+            .issues(&owner, &repo)
+            .add_labels(issue_number as u64, &labels)
+            .await
+    })
+    .await?;
+
+    fetch_single_issue(app, owner, repo, issue_number, cache).await
+}
+
+#[command]
+pub async fn remove_issue_label(
+    app: AppHandle,
+    owner: String,
+    repo: String,
+    issue_number: i64,
+    label: String,
+    cache: State<'_, IssuesCache>,
+) -> Result<IssueData, String> {
+    let octocrab = build_client(&app)?;
+
+    with_rate_limit(Resource::Core, || async {
+        octocrab
+            .issues(&owner, &repo)
+            .remove_label(issue_number as u64, &label)
+            .await
+    })
+    .await?;
+
+    fetch_single_issue(app, owner, repo, issue_number, cache).await
+}
+
+#[command]
+pub async fn add_issue_assignees(
+    app: AppHandle,
+    owner: String,
+    repo: String,
+    issue_number: i64,
+    assignees: Vec<String>,
+    cache: State<'_, IssuesCache>,
+) -> Result<IssueData, String> {
+    let octocrab = build_client(&app)?;
+
+    let assignees: Vec<&str> = assignees.iter().map(String::as_str).collect();
+    with_rate_limit(Resource::Core, || async {
+        octocrab
+            .issues(&owner, &repo)
+            .add_assignees(issue_number as u64, &assignees)
+            .await
+    })
+    .await?;
+
+    fetch_single_issue(app, owner, repo, issue_number, cache).await
+}
+
+#[command]
+pub async fn remove_issue_assignees(
+    app: AppHandle,
+    owner: String,
+    repo: String,
+    issue_number: i64,
+    assignees: Vec<String>,
+    cache: State<'_, IssuesCache>,
+) -> Result<IssueData, String> {
+    let octocrab = build_client(&app)?;
+
+    let route = format!(
+        "/repos/{}/{}/issues/{}/assignees",
+        owner, repo, issue_number
+    );
+    with_rate_limit(Resource::Core, || async {
+        octocrab
+            .delete::<Issue, _, _>(&route, Some(&json!({ "assignees": assignees })))
+            .await
+    })
+    .await?;
+
+    fetch_single_issue(app, owner, repo, issue_number, cache).await
+}
+
+/// Sets the issue's milestone, or clears it when `milestone` is `None`.
+#[command]
+pub async fn set_issue_milestone(
+    app: AppHandle,
+    owner: String,
+    repo: String,
+    issue_number: i64,
+    milestone: Option<i64>,
+    cache: State<'_, IssuesCache>,
+) -> Result<IssueData, String> {
+    let octocrab = build_client(&app)?;
+
+    // The update builder can't send `null`, which is how GitHub clears a milestone
+    let route = format!("/repos/{}/{}/issues/{}", owner, repo, issue_number);
+    with_rate_limit(Resource::Core, || async {
+        octocrab
+            .patch::<Issue, _, _>(&route, Some(&json!({ "milestone": milestone })))
+            .await
+    })
+    .await?;
+
+    fetch_single_issue(app, owner, repo, issue_number, cache).await
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LockReason {
+    OffTopic,
+    TooHeated,
+    Resolved,
+    Spam,
+}
+
+impl LockReason {
+    fn as_str(self) -> &'static str {
+        match self {
+            LockReason::OffTopic => "off-topic",
+            LockReason::TooHeated => "too heated",
+            LockReason::Resolved => "resolved",
+            LockReason::Spam => "spam",
+        }
+    }
+}
+
+/// Locks the conversation so only collaborators can comment.
+#[command]
+pub async fn lock_issue(
+    app: AppHandle,
+    owner: String,
+    repo: String,
+    issue_number: i64,
+    reason: Option<LockReason>,
+    cache: State<'_, IssuesCache>,
+) -> Result<IssueData, String> {
+    let octocrab = build_client(&app)?;
+
+    let route = format!("/repos/{}/{}/issues/{}/lock", owner, repo, issue_number);
+    let body = match reason {
+        Some(reason) => json!({ "lock_reason": reason.as_str() }),
+        None => json!({}),
+    };
+    with_rate_limit(Resource::Core, || async {
+        let response = octocrab._put(route.as_str(), Some(&body)).await?;
+        octocrab::map_github_error(response).await.map(drop)
+    })
+    .await?;
+
+    fetch_single_issue(app, owner, repo, issue_number, cache).await
+}
+
+#[command]
+pub async fn unlock_issue(
+    app: AppHandle,
+    owner: String,
+    repo: String,
+    issue_number: i64,
+    cache: State<'_, IssuesCache>,
+) -> Result<IssueData, String> {
+    let octocrab = build_client(&app)?;
+
+    let route = format!("/repos/{}/{}/issues/{}/lock", owner, repo, issue_number);
+    with_rate_limit(Resource::Core, || async {
+        let response = octocrab._delete(route.as_str(), None::<&()>).await?;
+        octocrab::map_github_error(response).await.map(drop)
+    })
+    .await?;
+
+    fetch_single_issue(app, owner, repo, issue_number, cache).await
+}
+
+// Pinning, transferring and deleting only exist in the GraphQL API
+const ISSUE_ID_QUERY: &str = r#"
+query($owner: String!, $name: String!, $number: Int!) {
+  repository(owner: $owner, name: $name) {
+    issue(number: $number) { id viewerCanDelete }
+  }
+}
+"#;
+
+const REPOSITORY_ID_QUERY: &str = r#"
+query($owner: String!, $name: String!) {
+  repository(owner: $owner, name: $name) { id }
+}
+"#;
+
+const PIN_ISSUE_MUTATION: &str = r#"
+mutation($issueId: ID!) {
+  pinIssue(input: {issueId: $issueId}) { issue { number } }
+}
+"#;
+
+const UNPIN_ISSUE_MUTATION: &str = r#"
+mutation($issueId: ID!) {
+  unpinIssue(input: {issueId: $issueId}) { issue { number } }
+}
+"#;
+
+const TRANSFER_ISSUE_MUTATION: &str = r#"
+mutation($issueId: ID!, $repositoryId: ID!) {
+  transferIssue(input: {issueId: $issueId, repositoryId: $repositoryId}) {
+    issue { number }
+  }
+}
+"#;
+
+const DELETE_ISSUE_MUTATION: &str = r#"
+mutation($issueId: ID!) {
+  deleteIssue(input: {issueId: $issueId}) { clientMutationId }
+}
+"#;
+
+#[derive(Deserialize)]
+struct RepositoryNodeData<T> {
+    repository: Option<T>,
+}
+
+#[derive(Deserialize)]
+struct IssueLookup {
+    issue: Option<IssueNodeId>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct IssueNodeId {
+    id: String,
+    viewer_can_delete: bool,
+}
+
+#[derive(Deserialize)]
+struct NodeId {
+    id: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TransferIssueData {
+    transfer_issue: TransferredIssue,
+}
+
+#[derive(Deserialize)]
+struct TransferredIssue {
+    issue: IssueNumber,
+}
+
+#[derive(Deserialize)]
+struct IssueNumber {
+    number: i64,
+}
+
+async fn issue_node(
+    client: &GraphqlClient,
+    owner: &str,
+    repo: &str,
+    issue_number: i64,
+) -> Result<IssueNodeId, String> {
+    let data: RepositoryNodeData<IssueLookup> = client
+        .query(
+            ISSUE_ID_QUERY,
+            json!({ "owner": owner, "name": repo, "number": issue_number }),
+        )
+        .await?;
+
+    data.repository
+        .and_then(|repository| repository.issue)
+        .ok_or_else(|| format!("Issue #{} not found in {}/{}", issue_number, owner, repo))
+}
+
+async fn set_pinned(
+    app: AppHandle,
+    owner: String,
+    repo: String,
+    issue_number: i64,
+    pinned: bool,
+    cache: State<'_, IssuesCache>,
+) -> Result<IssueData, String> {
+    let client = GraphqlClient::from_app(&app)?;
+    let issue = issue_node(&client, &owner, &repo, issue_number).await?;
+
+    let mutation = if pinned {
+        PIN_ISSUE_MUTATION
+    } else {
+        UNPIN_ISSUE_MUTATION
+    };
+    client
+        .mutate::<Value>(mutation, json!({ "issueId": issue.id }))
+        .await?;
+
+    let cache_key = format!("{}/{}", owner, repo);
+    let mut issue_data = fetch_single_issue(app, owner, repo, issue_number, cache.clone()).await?;
+    issue_data.pinned = Some(pinned);
+    cache.upsert_issue(&cache_key, issue_data.clone())?;
+
+    Ok(issue_data)
+}
+
+/// Pins the issue to the top of the repo's issue list. GitHub allows three per repo.
+#[command]
+pub async fn pin_issue(
+    app: AppHandle,
+    owner: String,
+    repo: String,
+    issue_number: i64,
+    cache: State<'_, IssuesCache>,
+) -> Result<IssueData, String> {
+    set_pinned(app, owner, repo, issue_number, true, cache).await
+}
+
+#[command]
+pub async fn unpin_issue(
+    app: AppHandle,
+    owner: String,
+    repo: String,
+    issue_number: i64,
+    cache: State<'_, IssuesCache>,
+) -> Result<IssueData, String> {
+    set_pinned(app, owner, repo, issue_number, false, cache).await
+}
+
+/// Moves the issue to another repo, which gives it a new number. The cached copy moves to
+/// the new repo's entry.
+#[command]
+pub async fn transfer_issue(
+    app: AppHandle,
+    owner: String,
+    repo: String,
+    issue_number: i64,
+    new_owner: String,
+    new_repo: String,
+    cache: State<'_, IssuesCache>,
+) -> Result<IssueData, String> {
+    let client = GraphqlClient::from_app(&app)?;
+    let issue = issue_node(&client, &owner, &repo, issue_number).await?;
+
+    let target: RepositoryNodeData<NodeId> = client
+        .query(
+            REPOSITORY_ID_QUERY,
+            json!({ "owner": new_owner, "name": new_repo }),
+        )
+        .await?;
+    let target = target
+        .repository
+        .ok_or_else(|| format!("Repository {}/{} not found", new_owner, new_repo))?;
+
+    let transferred: TransferIssueData = client
+        .mutate(
+            TRANSFER_ISSUE_MUTATION,
+            json!({ "issueId": issue.id, "repositoryId": target.id }),
+        )
+        .await?;
+
+    cache.remove_issue(&format!("{}/{}", owner, repo), issue_number)?;
+    fetch_single_issue(
+        app,
+        new_owner,
+        new_repo,
+        transferred.transfer_issue.issue.number,
+        cache,
+    )
+    .await
+}
+
+/// Deletes the issue for good. Only repo admins can, so the token's access is checked first.
+#[command]
+pub async fn delete_issue(
+    app: AppHandle,
+    owner: String,
+    repo: String,
+    issue_number: i64,
+    cache: State<'_, IssuesCache>,
+) -> Result<(), String> {
+    let client = GraphqlClient::from_app(&app)?;
+    let issue = issue_node(&client, &owner, &repo, issue_number).await?;
+    if !issue.viewer_can_delete {
+        return Err(format!(
+            "You don't have permission to delete issues in {}/{}",
+            owner, repo
+        ));
+    }
+
+    client
+        .mutate::<Value>(DELETE_ISSUE_MUTATION, json!({ "issueId": issue.id }))
+        .await?;
+
+    cache.remove_issue(&format!("{}/{}", owner, repo), issue_number)
+}
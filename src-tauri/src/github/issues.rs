@@ -1,15 +1,158 @@
+use super::comments::prefetch_comments_for;
 use super::github_client::get_client;
-use chrono::{DateTime, Duration, Utc};
-use octocrab::models::issues::Issue;
-use octocrab::params;
+use super::graphql::{self, GraphqlClient};
+use super::interactions::add_issue_comment;
+use super::milestones::MilestoneData;
+use super::rate_limit::{record_headers, with_rate_limit, with_retry, Classify, Failure, Resource};
+use super::tasks::{parse_tasks, TaskList};
+use crate::drafts::drafts::{clear_draft, DraftTarget};
+use crate::github::get_username;
+use crate::outbox::queue::{show_pending, write_or_queue, Mutation, Outbox, Written};
+use crate::search::index::SearchIndex;
+use crate::storage::store::IssueStore;
+use chrono::{DateTime, Duration, SecondsFormat, Utc};
+use http::header::{HeaderMap, HeaderValue, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use http::StatusCode;
+use octocrab::models::issues::{Issue, IssueStateReason};
+use octocrab::{FromResponse, Page};
 use serde::{Deserialize, Serialize};
-use std::sync::Mutex;
+use std::sync::{Mutex, OnceLock};
 use std::{collections::HashMap, fs, path::PathBuf, sync::Arc};
 use tauri::{command, AppHandle, Manager, State};
 
-#[derive(Debug, Default)]
+/// Upper bound on issues loaded per repo when the caller doesn't pass one.
+const DEFAULT_MAX_ISSUES: usize = 5000;
+/// Issues whose comments are loaded after a sync when asked to prefetch, most recently
+/// updated first. The rest load when opened.
+const PREFETCH_COMMENTS_LIMIT: usize = 30;
+
+#[derive(Debug, Default, Clone)]
 pub struct IssuesCache {
-    cache: Arc<Mutex<HashMap<String, (Vec<IssueData>, DateTime<Utc>)>>>,
+    cache: Arc<Mutex<HashMap<String, CachedIssues>>>,
+    store: Arc<OnceLock<IssueStore>>,
+    index: Arc<Mutex<SearchIndex>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct CachedIssues {
+    pub issues: Vec<IssueData>,
+    pub last_updated: DateTime<Utc>,
+    pub last_changed: DateTime<Utc>,
+    pub loaded: IssueStateFilter,
+    pub truncated: bool,
+    // The newest `updated_at` a listing sync returned, where the next incremental sync
+    // starts. Issues upserted one at a time or queued offline don't move it.
+    pub listed_until: Option<DateTime<Utc>>,
+    pub validators: Option<Validators>,
+}
+
+/// Response validators from the first page of the issue listing, used to send
+/// conditional requests on refresh.
+#[derive(Debug, Clone)]
+pub struct Validators {
+    pub route: String,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+}
+
+impl Validators {
+    pub(crate) fn from_headers(route: String, headers: &HeaderMap) -> Option<Self> {
+        let header = |name| {
+            headers
+                .get(name)
+                .and_then(|value: &HeaderValue| value.to_str().ok())
+                .map(String::from)
+        };
+
+        let etag = header(ETAG);
+        let last_modified = header(LAST_MODIFIED);
+        if etag.is_none() && last_modified.is_none() {
+            return None;
+        }
+
+        Some(Validators {
+            route,
+            etag,
+            last_modified,
+        })
+    }
+
+    pub(crate) fn to_headers(&self) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        if let Some(value) = self
+            .etag
+            .as_deref()
+            .and_then(|v| HeaderValue::from_str(v).ok())
+        {
+            headers.insert(IF_NONE_MATCH, value);
+        }
+        if let Some(value) = self
+            .last_modified
+            .as_deref()
+            .and_then(|v| HeaderValue::from_str(v).ok())
+        {
+            headers.insert(IF_MODIFIED_SINCE, value);
+        }
+        headers
+    }
+}
+
+/// Which API `fetch_issues` syncs through.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SyncEngine {
+    /// Issues through REST with ETags, comments loaded separately
+    #[default]
+    Rest,
+    /// Issues with their first comments, labels, milestone and reactions in batched queries
+    Graphql,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IssueStateFilter {
+    Open,
+    Closed,
+    #[default]
+    All,
+}
+
+impl IssueStateFilter {
+    pub fn from_str_lossy(value: &str) -> Self {
+        match value {
+            "open" => IssueStateFilter::Open,
+            "closed" => IssueStateFilter::Closed,
+            _ => IssueStateFilter::All,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            IssueStateFilter::Open => "open",
+            IssueStateFilter::Closed => "closed",
+            IssueStateFilter::All => "all",
+        }
+    }
+
+    fn matches(self, issue: &IssueData) -> bool {
+        match self {
+            IssueStateFilter::Open => issue.state == "open",
+            IssueStateFilter::Closed => issue.state == "closed",
+            IssueStateFilter::All => true,
+        }
+    }
+
+    fn covers(self, other: IssueStateFilter) -> bool {
+        self == IssueStateFilter::All || self == other
+    }
+
+    fn merge(self, other: IssueStateFilter) -> IssueStateFilter {
+        if self == other {
+            self
+        } else {
+            IssueStateFilter::All
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -24,9 +167,259 @@ impl Default for PinnedRepos {
 }
 
 impl IssuesCache {
-    pub fn get_cache(&self) -> &Arc<Mutex<HashMap<String, (Vec<IssueData>, DateTime<Utc>)>>> {
+    pub fn get_cache(&self) -> &Arc<Mutex<HashMap<String, CachedIssues>>> {
         &self.cache
     }
+
+    pub fn search_index(&self) -> &Arc<Mutex<SearchIndex>> {
+        &self.index
+    }
+
+    /// Loads everything from the on-disk store into memory and writes through to it from now on.
+    pub fn attach_store(&self, store: IssueStore) -> Result<(), String> {
+        let stored = store.load_all()?;
+        println!("Loaded {} repos from the issue store", stored.len());
+
+        if let Ok(mut index) = self.index.lock() {
+            for (key, cached) in &stored {
+                index.index_repo(key, &cached.issues);
+            }
+        }
+
+        self.cache.lock().map_err(|e| e.to_string())?.extend(stored);
+        self.store
+            .set(store)
+            .map_err(|_| "Issue store already attached".to_string())
+    }
+
+    /// Writes a repo's issues through to the store and the search index. Rewriting the
+    /// repo in SQLite is slow, so it runs on a blocking thread; don't hold the cache lock.
+    pub async fn persist_repo(&self, key: &str, cached: CachedIssues) {
+        if let Ok(mut index) = self.index.lock() {
+            index.index_repo(key, &cached.issues);
+        }
+
+        let store = self.store.clone();
+        let key = key.to_string();
+        let saved = tauri::async_runtime::spawn_blocking(move || match store.get() {
+            Some(store) => store.save_repo(&key, &cached),
+            None => Ok(()),
+        })
+        .await;
+        match saved {
+            Ok(Err(e)) => println!("{}", e),
+            Err(e) => println!("Failed to save issues: {}", e),
+            Ok(Ok(())) => {}
+        }
+    }
+
+    fn persist_sync_time(&self, key: &str, last_synced: DateTime<Utc>) {
+        if let Some(store) = self.store.get() {
+            if let Err(e) = store.touch_repo(key, last_synced) {
+                println!("{}", e);
+            }
+        }
+    }
+
+    /// Replaces or adds a single issue in the cache and the on-disk store.
+    pub fn upsert_issue(&self, key: &str, issue: IssueData) -> Result<(), String> {
+        let mut cache_guard = self.cache.lock().map_err(|e| e.to_string())?;
+
+        let cached = cache_guard
+            .entry(key.to_string())
+            .or_insert_with(|| CachedIssues {
+                issues: Vec::new(),
+                // A single issue isn't a full listing, so make the next fetch_issues reload the repo
+                last_updated: Utc::now() - Duration::days(1),
+                last_changed: Utc::now(),
+                loaded: IssueStateFilter::All,
+                truncated: true,
+                listed_until: None,
+                validators: None,
+            });
+
+        let mut issue = issue;
+        match cached.issues.iter_mut().find(|i| i.number == issue.number) {
+            Some(existing) => {
+                keep_pending_comments(existing, &mut issue);
+                if issue.pinned.is_none() {
+                    issue.pinned = existing.pinned;
+                }
+                *existing = issue.clone();
+            }
+            None => cached.issues.push(issue.clone()),
+        }
+
+        if let Ok(mut index) = self.index.lock() {
+            index.index_issue(key, &issue);
+        }
+
+        if let Some(store) = self.store.get() {
+            if let Err(e) = store.save_issue(key, cached, &issue) {
+                println!("{}", e);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Adds a comment written offline, or replaces the comment it edits. Returns the updated
+    /// issue, or `None` if it isn't cached.
+    pub fn apply_pending_comment(
+        &self,
+        key: &str,
+        number: i64,
+        comment: CommentData,
+    ) -> Result<Option<IssueData>, String> {
+        let issue = {
+            let cache_guard = self.cache.lock().map_err(|e| e.to_string())?;
+            let Some(mut issue) = cache_guard
+                .get(key)
+                .and_then(|cached| cached.issues.iter().find(|i| i.number == number))
+                .cloned()
+            else {
+                return Ok(None);
+            };
+
+            match issue.comments.iter_mut().find(|c| c.id == comment.id) {
+                Some(existing) => *existing = comment,
+                None => {
+                    issue.comments.push(comment);
+                    issue.comments_count += 1;
+                }
+            }
+            issue
+        };
+
+        self.upsert_issue(key, issue.clone())?;
+        Ok(Some(issue))
+    }
+
+    /// Clears the pending mark once the outbox has sent a comment. Placeholders for new
+    /// comments are dropped, the next load brings the real one.
+    pub fn resolve_pending_comment(
+        &self,
+        key: &str,
+        number: i64,
+        comment_id: i64,
+    ) -> Result<(), String> {
+        let mut cache_guard = self.cache.lock().map_err(|e| e.to_string())?;
+        let Some(cached) = cache_guard.get_mut(key) else {
+            return Ok(());
+        };
+        let Some(issue) = cached.issues.iter_mut().find(|i| i.number == number) else {
+            return Ok(());
+        };
+
+        if comment_id < 0 {
+            let before = issue.comments.len();
+            issue.comments.retain(|c| c.id != comment_id);
+            if issue.comments.len() < before {
+                issue.comments_count = issue.comments_count.saturating_sub(1);
+            }
+        } else if let Some(comment) = issue.comments.iter_mut().find(|c| c.id == comment_id) {
+            comment.pending = false;
+        }
+        let issue = issue.clone();
+
+        if let Some(store) = self.store.get() {
+            if let Err(e) = store.save_issue(key, cached, &issue) {
+                println!("{}", e);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Removes an issue from the cache, the store and the search index.
+    pub fn remove_issue(&self, key: &str, number: i64) -> Result<(), String> {
+        let mut cache_guard = self.cache.lock().map_err(|e| e.to_string())?;
+        if let Some(cached) = cache_guard.get_mut(key) {
+            cached.issues.retain(|issue| issue.number != number);
+        }
+
+        if let Ok(mut index) = self.index.lock() {
+            index.remove(&(key.to_string(), number));
+        }
+        if let Some(store) = self.store.get() {
+            if let Err(e) = store.delete_issue(key, number) {
+                println!("{}", e);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Stores the result of loading an issue's comments. `updated_at` is the issue's
+    /// timestamp when the load started; if it has moved on since, the comments stay marked stale.
+    pub fn set_issue_comments(
+        &self,
+        key: &str,
+        number: i64,
+        updated_at: &str,
+        comments: Result<Vec<CommentData>, String>,
+    ) -> Result<Option<IssueData>, String> {
+        let mut cache_guard = self.cache.lock().map_err(|e| e.to_string())?;
+        let Some(cached) = cache_guard.get_mut(key) else {
+            return Ok(None);
+        };
+        let Some(issue) = cached.issues.iter_mut().find(|i| i.number == number) else {
+            return Ok(None);
+        };
+
+        match comments {
+            Ok(comments) => {
+                let known = issue.clone();
+                issue.comments_count = comments.len() as i64;
+                issue.comments = comments;
+                keep_pending_comments(&known, issue);
+                issue.comments_loaded = issue.updated_at == updated_at;
+                issue.comments_error = None;
+            }
+            Err(e) => issue.comments_error = Some(e),
+        }
+        let issue = issue.clone();
+
+        if let Ok(mut index) = self.index.lock() {
+            index.index_issue(key, &issue);
+        }
+        if let Some(store) = self.store.get() {
+            if let Err(e) = store.save_issue(key, cached, &issue) {
+                println!("{}", e);
+            }
+        }
+
+        Ok(Some(issue))
+    }
+}
+
+/// Carries comments written offline over to a freshly loaded copy of the issue, replacing
+/// the loaded version of a comment with its pending edit.
+fn keep_pending_comments(known: &IssueData, issue: &mut IssueData) {
+    for comment in known.comments.iter().filter(|comment| comment.pending) {
+        match issue.comments.iter_mut().find(|c| c.id == comment.id) {
+            Some(existing) => *existing = comment.clone(),
+            None => {
+                issue.comments.push(comment.clone());
+                issue.comments_count += 1;
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ReactionSummary {
+    pub total_count: i64,
+    #[serde(rename = "+1")]
+    pub plus_one: i64,
+    #[serde(rename = "-1")]
+    pub minus_one: i64,
+    pub laugh: i64,
+    pub hooray: i64,
+    pub confused: i64,
+    pub heart: i64,
+    pub rocket: i64,
+    pub eyes: i64,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -36,6 +429,10 @@ pub struct CommentData {
     pub created_at: String,
     pub updated_at: Option<String>,
     pub author: String,
+    pub reactions: Option<ReactionSummary>,
+    // Written offline and waiting in the outbox
+    pub pending: bool,
+    pub tasks: TaskList,
 }
 
 #[derive(Debug, Clone, serde::Serialize)]
@@ -43,19 +440,68 @@ pub struct IssueData {
     pub number: i64,
     pub title: String,
     pub state: String,
+    pub state_reason: Option<String>,
     pub created_at: String,
+    pub updated_at: String,
     pub body: Option<String>,
     pub labels: Vec<String>,
     pub assignees: Vec<String>,
+    pub milestone: Option<MilestoneData>,
     pub comments: Vec<CommentData>,
+    pub comments_count: i64,
+    // False while `comments` is missing or older than the issue itself
+    pub comments_loaded: bool,
+    pub comments_error: Option<String>,
     pub creator: String,
+    pub is_pull_request: bool,
+    pub reactions: Option<ReactionSummary>,
+    // Created offline and waiting in the outbox, numbered below zero until then
+    pub pending: bool,
+    pub locked: bool,
+    pub lock_reason: Option<String>,
+    // Only the GraphQL sync reports pins, `None` until it or the app has seen one
+    pub pinned: Option<bool>,
+    // Checklist items in the body
+    pub tasks: TaskList,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CacheStatus {
     cached: bool,
     last_updated: Option<String>,
+    unchanged_since: Option<String>,
     etag: Option<String>,
+    last_modified: Option<String>,
+    truncated: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RepoSyncStatus {
+    repo: String,
+    last_synced: String,
+    last_changed: String,
+    issue_count: usize,
+    stale: bool,
+}
+
+/// An issue as the REST API returns it, with the reaction counts and lock reason octocrab's
+/// model leaves out.
+#[derive(Debug, Deserialize)]
+pub struct RestIssue {
+    #[serde(flatten)]
+    pub issue: Issue,
+    pub reactions: Option<ReactionSummary>,
+    pub active_lock_reason: Option<String>,
+}
+
+impl From<RestIssue> for IssueData {
+    fn from(rest: RestIssue) -> Self {
+        IssueData {
+            reactions: rest.reactions,
+            lock_reason: rest.active_lock_reason,
+            ..IssueData::from(rest.issue)
+        }
+    }
 }
 
 impl From<Issue> for IssueData {
@@ -68,12 +514,31 @@ impl From<Issue> for IssueData {
                 octocrab::models::IssueState::Closed => String::from("closed"),
                 _ => String::from("unknown"),
             },
+            state_reason: issue.state_reason.map(|reason| match reason {
+                IssueStateReason::Completed => String::from("completed"),
+                IssueStateReason::NotPlanned => String::from("not_planned"),
+                IssueStateReason::Reopened => String::from("reopened"),
+                _ => String::from("unknown"),
+            }),
             created_at: issue.created_at.to_rfc3339(),
+            updated_at: issue.updated_at.to_rfc3339(),
+            tasks: parse_tasks(issue.body.as_deref()),
             body: issue.body,
             labels: issue.labels.into_iter().map(|label| label.name).collect(),
             assignees: issue.assignees.into_iter().map(|user| user.login).collect(),
+            milestone: issue.milestone.map(MilestoneData::from),
             comments: Vec::new(),
+            comments_count: issue.comments as i64,
+            // Nothing to load when there are no comments
+            comments_loaded: issue.comments == 0,
+            comments_error: None,
             creator: issue.user.login,
+            is_pull_request: issue.pull_request.is_some(),
+            reactions: None,
+            pending: false,
+            locked: issue.locked,
+            lock_reason: None,
+            pinned: None,
         }
     }
 }
@@ -88,19 +553,26 @@ pub async fn check_cache_status<'a>(
     let cache_key = format!("{}/{}", owner, repo);
     let cache_guard = cache.cache.lock().map_err(|e| e.to_string())?;
 
-    if let Some((_, last_updated)) = cache_guard.get(&cache_key) {
-        println!("Cache found with last update: {}", last_updated);
+    if let Some(cached) = cache_guard.get(&cache_key) {
+        println!("Cache found with last update: {}", cached.last_updated);
+        let validators = cached.validators.as_ref();
         Ok(CacheStatus {
             cached: true,
-            last_updated: Some(last_updated.to_string()),
-            etag: None,
+            last_updated: Some(cached.last_updated.to_string()),
+            unchanged_since: Some(cached.last_changed.to_string()),
+            etag: validators.and_then(|v| v.etag.clone()),
+            last_modified: validators.and_then(|v| v.last_modified.clone()),
+            truncated: cached.truncated,
         })
     } else {
         println!("No cache found for {}/{}", owner, repo);
         Ok(CacheStatus {
             cached: false,
             last_updated: None,
+            unchanged_since: None,
             etag: None,
+            last_modified: None,
+            truncated: false,
         })
     }
 }
@@ -111,91 +583,322 @@ pub async fn fetch_issues(
     repo: String,
     cache: State<'_, IssuesCache>,
     force_refresh: bool,
+    state: Option<IssueStateFilter>,
+    max_issues: Option<usize>,
+    prefetch_comments: Option<bool>,
+    engine: Option<SyncEngine>,
+    app: AppHandle,
 ) -> Result<Vec<IssueData>, String> {
     println!("Fetching issues for {}/{}", owner, repo);
 
     let cache_key = format!("{}/{}", owner, repo);
+    let state = state.unwrap_or_default();
+    let max_issues = max_issues.unwrap_or(DEFAULT_MAX_ISSUES);
+    let engine = engine.unwrap_or_default();
 
     // Check cache
-    if !force_refresh {
+    let mut since = None;
+    let mut listing = state;
+    let mut cached_validators = None;
+    let mut known_comments: HashMap<i64, IssueData> = HashMap::new();
+    {
         let cache_guard = cache.cache.lock().map_err(|e| e.to_string())?;
-        if let Some((cached_issues, last_updated)) = cache_guard.get(&cache_key) {
-            if Utc::now() - *last_updated < Duration::minutes(5) {
-                println!("Returning cached issues");
-                return Ok(cached_issues.clone());
+        if let Some(cached) = cache_guard.get(&cache_key) {
+            if cached.loaded.covers(state) {
+                if !force_refresh && Utc::now() - cached.last_updated < Duration::minutes(5) {
+                    println!("Returning cached issues");
+                    return Ok(filter_issues(&cached.issues, state));
+                }
+
+                // A truncated cache can't be extended incrementally, so reload all of it,
+                // which also tells whether it's still truncated
+                if cached.truncated {
+                    listing = cached.loaded;
+                } else {
+                    since = cached.listed_until;
+                }
+                cached_validators = cached.validators.clone();
             }
+
+            known_comments = cached
+                .issues
+                .iter()
+                .map(|issue| (issue.number, issue.clone()))
+                .collect();
         }
     }
 
     println!("Getting GitHub client...");
     let octocrab = get_client()?;
 
-    println!("Fetching issues from GitHub API...");
-    let mut all_issues = Vec::new();
-    let page = match octocrab
-        .issues(&owner, &repo)
-        .list()
-        .state(params::State::All)
-        .per_page(100)
-        .send()
-        .await
-    {
-        Ok(page) => {
-            // println!("Got {} issues", page.items.len());
-            page
+    let (mut processed_issues, truncated, validators) = match engine {
+        SyncEngine::Graphql => {
+            println!("Fetching issues from GitHub GraphQL API...");
+            let client = GraphqlClient::from_app(&app)?;
+            match graphql::fetch_issues(&client, &owner, &repo, listing, since, max_issues).await {
+                Ok(Ok((issues, truncated))) => (issues, truncated, None),
+                // A revoked token or a deleted repo would only be hidden by the stored copy
+                Ok(Err(e)) => return Err(e),
+                Err(e) => {
+                    println!("Error fetching issues: {}", e);
+                    return stored_issues(&cache, &cache_key, state, e);
+                }
+            }
         }
-        Err(e) => {
-            println!("Error fetching issues: {}", e);
-            return Err(e.to_string());
+        SyncEngine::Rest => {
+            // Sorted by last update so any change to an issue also changes the first page's ETag.
+            // Incremental syncs ask for every state so issues that were closed or reopened move over.
+            let route = match since {
+                Some(since) => format!(
+                    "/repos/{}/{}/issues?state=all&sort=updated&direction=desc&per_page=100&since={}",
+                    owner,
+                    repo,
+                    since.to_rfc3339_opts(SecondsFormat::Secs, true)
+                ),
+                None => format!(
+                    "/repos/{}/{}/issues?state={}&sort=updated&direction=desc&per_page=100",
+                    owner,
+                    repo,
+                    listing.as_str()
+                ),
+            };
+            let conditional_headers = cached_validators
+                .filter(|validators| validators.route == route)
+                .map(|validators| validators.to_headers());
+
+            println!("Fetching issues from GitHub API...");
+            let response = match with_retry(Resource::Core, || async {
+                let response = match octocrab
+                    ._get_with_headers(route.as_str(), conditional_headers.clone())
+                    .await
+                {
+                    Err(e) if e.classify() == Failure::Fatal => return Ok(Err(e)),
+                    result => result?,
+                };
+                record_headers(response.headers());
+                if response.status() == StatusCode::NOT_MODIFIED {
+                    return Ok(Ok(response));
+                }
+                match octocrab::map_github_error(response).await {
+                    Err(e) if e.classify() == Failure::Fatal => Ok(Err(e)),
+                    result => result.map(Ok),
+                }
+            })
+            .await
+            {
+                Ok(Ok(response)) => response,
+                Ok(Err(e)) => return Err(e.to_string()),
+                Err(e) => {
+                    println!("Error fetching issues: {}", e);
+                    // Serve whatever we have stored when GitHub can't be reached
+                    return stored_issues(&cache, &cache_key, state, e);
+                }
+            };
+
+            if response.status() == StatusCode::NOT_MODIFIED {
+                println!("Issues unchanged since last fetch");
+                let mut cache_guard = cache.cache.lock().map_err(|e| e.to_string())?;
+                if let Some(cached) = cache_guard.get_mut(&cache_key) {
+                    cached.last_updated = Utc::now();
+                    cache.persist_sync_time(&cache_key, cached.last_updated);
+                    return Ok(filter_issues(&cached.issues, state));
+                }
+                return Err("Received 304 Not Modified without cached issues".to_string());
+            }
+
+            let validators = Validators::from_headers(route, response.headers());
+            let mut page = Page::<RestIssue>::from_response(response)
+                .await
+                .map_err(|e| e.to_string())?;
+
+            let mut fetched: Vec<RestIssue> = Vec::new();
+            let mut truncated = false;
+            loop {
+                fetched.extend(page.take_items());
+
+                if fetched.len() >= max_issues {
+                    truncated = fetched.len() > max_issues || page.next.is_some();
+                    fetched.truncate(max_issues);
+                    break;
+                }
+
+                match with_retry(Resource::Core, || {
+                    octocrab.get_page::<RestIssue>(&page.next)
+                })
+                .await?
+                {
+                    Some(next_page) => page = next_page,
+                    None => break,
+                }
+            }
+
+            println!("Processing {} issues...", fetched.len());
+            let issues: Vec<IssueData> = fetched.into_iter().map(IssueData::from).collect();
+            (issues, truncated, validators)
         }
     };
 
-    println!("Processing issues...");
-    let mut processed_issues: Vec<IssueData> =
-        page.items.into_iter().map(IssueData::from).collect();
+    if truncated {
+        println!(
+            "Stopped after {} issues for {}/{}, more are available",
+            max_issues, owner, repo
+        );
+    }
 
-    // println!("Fetching comments for {} issues...", processed_issues.len());
+    let mut changed = 0;
     for issue in &mut processed_issues {
-        // println!("Fetching comments for issue #{}", issue.number);
-        match octocrab
-            .issues(&owner, &repo)
-            .list_comments(issue.number as u64)
-            .send()
-            .await
-        {
-            Ok(comments) => {
-                // println!(
-                //     "Got {} comments for issue #{}",
-                //     comments.items.len(),
-                //     issue.number
-                // );
-                issue.comments = comments
-                    .items
-                    .into_iter()
-                    .map(|comment| CommentData {
-                        id: comment.id.0.try_into().unwrap_or_default(),
-                        body: comment.body.unwrap_or_default(),
-                        created_at: comment.created_at.to_rfc3339(),
-                        updated_at: comment.updated_at.map(|t| t.to_rfc3339()),
-                        author: comment.user.login,
-                    })
-                    .collect();
+        // Keep the comments we already have, they're refreshed separately by the prefetch
+        if let Some(known) = known_comments.get(&issue.number) {
+            let unchanged = known.updated_at == issue.updated_at;
+            if unchanged && known.comments_loaded && !issue.comments_loaded {
+                issue.comments = known.comments.clone();
+                issue.comments_loaded = true;
+            } else if issue.comments.is_empty() && !issue.comments_loaded {
+                issue.comments = known.comments.clone();
+            }
+            // Keep the last known reactions when the listing didn't report any
+            if issue.reactions.is_none() {
+                issue.reactions = known.reactions.clone();
+            }
+            if issue.pinned.is_none() {
+                issue.pinned = known.pinned;
             }
-            Err(e) => {
-                println!("Error fetching comments for issue #{}: {}", issue.number, e);
-                return Err(e.to_string());
+            keep_pending_comments(known, issue);
+            if unchanged {
+                continue;
             }
         }
+        changed += 1;
     }
+    println!("{} issues changed since last sync", changed);
 
-    all_issues.extend(processed_issues.clone());
+    let listed_until = processed_issues
+        .iter()
+        .filter_map(|issue| DateTime::parse_from_rfc3339(&issue.updated_at).ok())
+        .max()
+        .map(|updated_at| updated_at.with_timezone(&Utc));
+
+    // Issues created offline aren't on GitHub yet, so keep them until the outbox sends them
+    let pending_issues: Vec<IssueData> = known_comments
+        .into_values()
+        .filter(|issue| issue.pending)
+        .collect();
+    processed_issues.extend(pending_issues);
 
-    // Update cache
     let mut cache_guard = cache.cache.lock().map_err(|e| e.to_string())?;
-    cache_guard.insert(cache_key, (processed_issues, Utc::now()));
+    let previous = cache_guard.remove(&cache_key);
+    let last_changed = match &previous {
+        Some(cached) if changed == 0 => cached.last_changed,
+        _ => Utc::now(),
+    };
+
+    let entry = match previous {
+        // Merge the changed issues into what we already hold
+        Some(mut cached) if since.is_some() => {
+            for issue in processed_issues {
+                match cached.issues.iter_mut().find(|i| i.number == issue.number) {
+                    Some(existing) => *existing = issue,
+                    None => cached.issues.push(issue),
+                }
+            }
+            cached
+                .issues
+                .sort_by(|a, b| b.updated_at.cmp(&a.updated_at));
+            CachedIssues {
+                issues: cached.issues,
+                last_updated: Utc::now(),
+                last_changed,
+                loaded: cached.loaded,
+                truncated,
+                listed_until: listed_until.max(cached.listed_until),
+                validators,
+            }
+        }
+        // Keep issues of the other state if only one side was loaded
+        Some(mut cached) if listing != IssueStateFilter::All => {
+            cached.issues.retain(|issue| !listing.matches(issue));
+            cached.issues.extend(processed_issues);
+            CachedIssues {
+                issues: cached.issues,
+                last_updated: Utc::now(),
+                last_changed,
+                loaded: cached.loaded.merge(listing),
+                // Relisting the loaded state settles it, the other state may still be cut short
+                truncated: if cached.loaded == listing {
+                    truncated
+                } else {
+                    cached.truncated || truncated
+                },
+                // The other state was listed up to its own point, start from the older one
+                listed_until: match (cached.listed_until, listed_until) {
+                    (Some(a), Some(b)) => Some(a.min(b)),
+                    _ => None,
+                },
+                validators,
+            }
+        }
+        _ => CachedIssues {
+            issues: processed_issues,
+            last_updated: Utc::now(),
+            last_changed,
+            loaded: listing,
+            truncated,
+            listed_until,
+            validators,
+        },
+    };
+    let issues = filter_issues(&entry.issues, state);
+    let mut pending: Vec<&IssueData> = entry
+        .issues
+        .iter()
+        .filter(|issue| !issue.comments_loaded && !issue.pending)
+        .collect();
+    pending.sort_by(|a, b| b.updated_at.cmp(&a.updated_at));
+    let pending: Vec<i64> = pending
+        .into_iter()
+        .take(PREFETCH_COMMENTS_LIMIT)
+        .map(|issue| issue.number)
+        .collect();
+    cache_guard.insert(cache_key.clone(), entry.clone());
+    drop(cache_guard);
+    cache.persist_repo(&cache_key, entry).await;
 
-    println!("Returning {} issues", all_issues.len());
-    Ok(all_issues)
+    if prefetch_comments.unwrap_or(false) && !pending.is_empty() {
+        println!("Prefetching comments for {} issues", pending.len());
+        let cache = cache.inner().clone();
+        tauri::async_runtime::spawn(async move {
+            prefetch_comments_for(&cache, &octocrab, &owner, &repo, pending).await;
+        });
+    }
+
+    println!("Returning {} issues", issues.len());
+    Ok(issues)
+}
+
+/// Falls back to the stored issues for a repo when a sync fails.
+fn stored_issues(
+    cache: &IssuesCache,
+    cache_key: &str,
+    state: IssueStateFilter,
+    error: String,
+) -> Result<Vec<IssueData>, String> {
+    let cache_guard = cache.cache.lock().map_err(|e| e.to_string())?;
+    match cache_guard.get(cache_key) {
+        Some(cached) => {
+            println!("Returning stored issues from {}", cached.last_updated);
+            Ok(filter_issues(&cached.issues, state))
+        }
+        None => Err(error),
+    }
+}
+
+/// Pull requests share the cache with issues but are listed separately by `fetch_pull_requests`.
+fn filter_issues(issues: &[IssueData], state: IssueStateFilter) -> Vec<IssueData> {
+    issues
+        .iter()
+        .filter(|issue| !issue.is_pull_request && state.matches(issue))
+        .cloned()
+        .collect()
 }
 
 #[command]
@@ -209,8 +912,9 @@ pub async fn get_cached_issue(
 
     let cache_guard = cache.cache.lock().map_err(|e| e.to_string())?;
 
-    if let Some((cached_issues, _)) = cache_guard.get(&cache_key) {
-        return Ok(cached_issues
+    if let Some(cached) = cache_guard.get(&cache_key) {
+        return Ok(cached
+            .issues
             .iter()
             .find(|issue| issue.number == issue_number)
             .cloned());
@@ -219,6 +923,28 @@ pub async fn get_cached_issue(
     Ok(None)
 }
 
+/// Reports when each cached repo was last synced, so stale data can be flagged.
+#[command]
+pub async fn get_repo_sync_status(
+    cache: State<'_, IssuesCache>,
+) -> Result<Vec<RepoSyncStatus>, String> {
+    let cache_guard = cache.cache.lock().map_err(|e| e.to_string())?;
+
+    let mut statuses: Vec<RepoSyncStatus> = cache_guard
+        .iter()
+        .map(|(repo, cached)| RepoSyncStatus {
+            repo: repo.clone(),
+            last_synced: cached.last_updated.to_rfc3339(),
+            last_changed: cached.last_changed.to_rfc3339(),
+            issue_count: cached.issues.len(),
+            stale: Utc::now() - cached.last_updated >= Duration::minutes(5),
+        })
+        .collect();
+    statuses.sort_by(|a, b| a.repo.cmp(&b.repo));
+
+    Ok(statuses)
+}
+
 fn get_pinned_path(app: &AppHandle) -> Result<PathBuf, String> {
     let config_dir = app
         .path()
@@ -255,21 +981,87 @@ pub async fn save_pinned_repos(app: AppHandle, repos: Vec<String>) -> Result<(),
     fs::write(&pinned_path, json).map_err(|e| e.to_string())
 }
 
+/// A newly opened issue. `link_error` says why it couldn't be marked as a duplicate, the
+/// issue was created either way.
+#[derive(Debug, Serialize)]
+pub struct CreatedIssue {
+    #[serde(flatten)]
+    pub issue: IssueData,
+    pub link_error: Option<String>,
+}
+
+/// Opens an issue. With `duplicate_of`, also comments that it duplicates that issue so
+/// GitHub links the two.
 #[command]
 pub async fn create_new_issue(
+    app: AppHandle,
     owner: &str,
     repo: &str,
     title: &str,
     body: &str,
-) -> Result<octocrab::models::issues::Issue, String> {
+    duplicate_of: Option<i64>,
+    cache: State<'_, IssuesCache>,
+) -> Result<CreatedIssue, String> {
     let octocrab = get_client()?;
-    let issue = octocrab
-        .issues(owner, repo)
-        .create(title)
-        .body(body)
-        .send()
-        .await
-        .map_err(|e| e.to_string())?;
-
-    Ok(issue)
+    let mutation = Mutation::CreateIssue {
+        owner: owner.to_string(),
+        repo: repo.to_string(),
+        title: title.to_string(),
+        body: body.to_string(),
+    };
+
+    let written = write_or_queue(&app.state::<Outbox>(), mutation, || {
+        with_rate_limit(Resource::Core, || async {
+            octocrab
+                .issues(owner, repo)
+                .create(title)
+                .body(body)
+                .send()
+                .await
+        })
+    })
+    .await?;
+    let cache_key = format!("{}/{}", owner, repo);
+    clear_draft(&app, &cache_key, DraftTarget::Issue);
+    let issue = match written {
+        Written::Sent(issue) => {
+            let issue = IssueData::from(issue);
+            cache.upsert_issue(&cache_key, issue.clone())?;
+            issue
+        }
+        // Queued behind the issue, and renumbered once it's sent
+        Written::Queued(entry) => show_pending(&cache, &entry, &get_username(app.clone())?)?,
+    };
+
+    let Some(original) = duplicate_of else {
+        return Ok(CreatedIssue {
+            issue,
+            link_error: None,
+        });
+    };
+    match add_issue_comment(
+        app,
+        owner.to_string(),
+        repo.to_string(),
+        issue.number,
+        format!("Duplicate of #{}", original),
+        cache,
+    )
+    .await
+    {
+        Ok(issue) => Ok(CreatedIssue {
+            issue,
+            link_error: None,
+        }),
+        Err(e) => {
+            println!(
+                "Failed to mark #{} as a duplicate of #{}: {}",
+                issue.number, original, e
+            );
+            Ok(CreatedIssue {
+                issue,
+                link_error: Some(e),
+            })
+        }
+    }
 }
@@ -0,0 +1,157 @@
+use super::github_client::get_client;
+use super::issues::IssueStateFilter;
+use super::rate_limit::{all_pages, with_rate_limit, with_retry, Resource};
+use octocrab::models::Milestone;
+use octocrab::Page;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use tauri::command;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MilestoneData {
+    pub number: i64,
+    pub title: String,
+    pub description: Option<String>,
+    pub state: String,
+    pub due_on: Option<String>,
+    pub open_issues: i64,
+    pub closed_issues: i64,
+    pub completion: f64,
+}
+
+/// Percentage of a milestone's issues that are closed, 0 when it has none.
+pub fn completion(open_issues: i64, closed_issues: i64) -> f64 {
+    let total = open_issues + closed_issues;
+    if total == 0 {
+        0.0
+    } else {
+        closed_issues as f64 / total as f64 * 100.0
+    }
+}
+
+impl From<Milestone> for MilestoneData {
+    fn from(milestone: Milestone) -> Self {
+        let open_issues = milestone.open_issues.unwrap_or_default();
+        let closed_issues = milestone.closed_issues.unwrap_or_default();
+
+        MilestoneData {
+            number: milestone.number,
+            title: milestone.title,
+            description: milestone.description,
+            state: milestone.state.unwrap_or_else(|| "open".to_string()),
+            due_on: milestone.due_on.map(|t| t.to_rfc3339()),
+            open_issues,
+            closed_issues,
+            completion: completion(open_issues, closed_issues),
+        }
+    }
+}
+
+/// GitHub wants a full timestamp, but a milestone due date is usually picked as a day.
+fn normalize_due_on(due_on: &str) -> Value {
+    match due_on.trim() {
+        "" => Value::Null,
+        date if date.len() == 10 => json!(format!("{}T00:00:00Z", date)),
+        timestamp => json!(timestamp),
+    }
+}
+
+#[command]
+pub async fn list_milestones(
+    owner: String,
+    repo: String,
+    state: Option<IssueStateFilter>,
+) -> Result<Vec<MilestoneData>, String> {
+    let octocrab = get_client()?;
+    let state = state.unwrap_or(IssueStateFilter::Open);
+
+    let route = format!(
+        "/repos/{}/{}/milestones?state={}&sort=due_on&direction=asc&per_page=100",
+        owner,
+        repo,
+        state.as_str()
+    );
+    let page: Page<Milestone> =
+        with_retry(Resource::Core, || octocrab.get(&route, None::<&()>)).await?;
+    let milestones = all_pages(&octocrab, page).await?;
+
+    Ok(milestones.into_iter().map(MilestoneData::from).collect())
+}
+
+#[command]
+pub async fn create_milestone(
+    owner: String,
+    repo: String,
+    title: String,
+    description: Option<String>,
+    due_on: Option<String>,
+) -> Result<MilestoneData, String> {
+    let octocrab = get_client()?;
+
+    let mut body = Map::new();
+    body.insert("title".to_string(), json!(title));
+    if let Some(description) = description {
+        body.insert("description".to_string(), json!(description));
+    }
+    if let Some(due_on) = due_on {
+        body.insert("due_on".to_string(), normalize_due_on(&due_on));
+    }
+
+    let route = format!("/repos/{}/{}/milestones", owner, repo);
+    let milestone: Milestone =
+        with_rate_limit(Resource::Core, || octocrab.post(&route, Some(&body))).await?;
+
+    Ok(MilestoneData::from(milestone))
+}
+
+/// Updates only the fields that are passed. An empty `due_on` clears the due date.
+#[command]
+pub async fn update_milestone(
+    owner: String,
+    repo: String,
+    milestone_number: i64,
+    title: Option<String>,
+    description: Option<String>,
+    due_on: Option<String>,
+    state: Option<String>,
+) -> Result<MilestoneData, String> {
+    let octocrab = get_client()?;
+
+    let mut body = Map::new();
+    if let Some(title) = title {
+        body.insert("title".to_string(), json!(title));
+    }
+    if let Some(description) = description {
+        body.insert("description".to_string(), json!(description));
+    }
+    if let Some(due_on) = due_on {
+        body.insert("due_on".to_string(), normalize_due_on(&due_on));
+    }
+    if let Some(state) = state {
+        body.insert("state".to_string(), json!(state));
+    }
+
+    let route = format!("/repos/{}/{}/milestones/{}", owner, repo, milestone_number);
+    let milestone: Milestone =
+        with_rate_limit(Resource::Core, || octocrab.patch(&route, Some(&body))).await?;
+
+    Ok(MilestoneData::from(milestone))
+}
+
+#[command]
+pub async fn close_milestone(
+    owner: String,
+    repo: String,
+    milestone_number: i64,
+) -> Result<MilestoneData, String> {
+    update_milestone(
+        owner,
+        repo,
+        milestone_number,
+        None,
+        None,
+        None,
+        Some("closed".to_string()),
+    )
+    .await
+}
@@ -1,8 +1,21 @@
+pub mod comments;
+pub mod crossref;
 pub mod github_client;
+pub mod graphql;
+pub mod inbox;
 pub mod interactions;
 pub mod issues;
+pub mod milestones;
+pub mod notifications;
 pub mod oauth;
+pub mod pulls;
+pub mod rate_limit;
+pub mod reactions;
+pub mod references;
 pub mod repos;
+pub mod tasks;
+pub mod templates;
+pub mod timeline;
 pub mod types;
 
 // pub use oauth::get_token;
@@ -0,0 +1,373 @@
+use super::github_client::get_client;
+use super::issues::Validators;
+use super::oauth::get_username;
+use super::rate_limit::{record_headers, with_rate_limit, with_retry, Resource};
+use chrono::{DateTime, Duration, Utc};
+use http::{HeaderMap, StatusCode};
+use octocrab::{FromResponse, Octocrab, Page};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::sync::Mutex;
+use std::{collections::HashMap, sync::Arc};
+use tauri::{command, AppHandle, State};
+
+const MAX_NOTIFICATIONS: usize = 500;
+/// Used until GitHub tells us otherwise through `X-Poll-Interval`.
+const DEFAULT_POLL_INTERVAL_SECS: i64 = 60;
+
+#[derive(Debug, Default)]
+pub struct NotificationsCache {
+    // Keyed by login, like the inbox
+    cache: Arc<Mutex<HashMap<String, CachedNotifications>>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct CachedNotifications {
+    /// The filtered listing these threads came from.
+    pub route: String,
+    pub threads: Vec<NotificationThread>,
+    pub validators: Option<Validators>,
+    pub poll_interval: i64,
+    pub last_polled: DateTime<Utc>,
+}
+
+impl CachedNotifications {
+    fn next_poll(&self) -> DateTime<Utc> {
+        self.last_polled + Duration::seconds(self.poll_interval)
+    }
+
+    fn to_list(&self) -> NotificationList {
+        NotificationList {
+            unread_count: self.threads.iter().filter(|thread| thread.unread).count(),
+            threads: self.threads.clone(),
+            poll_interval: self.poll_interval,
+            next_poll_at: self.next_poll().to_rfc3339(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct NotificationThread {
+    pub id: String,
+    pub repo: String,
+    /// Why the user was notified, such as `mention`, `review_requested` or `subscribed`.
+    pub reason: String,
+    pub unread: bool,
+    pub updated_at: String,
+    pub last_read_at: Option<String>,
+    pub subject: NotificationSubject,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct NotificationSubject {
+    pub title: String,
+    /// `Issue`, `PullRequest`, `Release`, `Discussion` and so on.
+    pub kind: String,
+    /// The issue or pull request number, when the subject is one.
+    pub number: Option<i64>,
+    pub url: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct NotificationList {
+    pub threads: Vec<NotificationThread>,
+    pub unread_count: usize,
+    /// Seconds GitHub asks clients to wait between polls.
+    pub poll_interval: i64,
+    pub next_poll_at: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawThread {
+    id: String,
+    unread: bool,
+    reason: String,
+    updated_at: String,
+    last_read_at: Option<String>,
+    subject: RawSubject,
+    repository: RawRepository,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawSubject {
+    title: String,
+    url: Option<String>,
+    #[serde(rename = "type")]
+    kind: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawRepository {
+    full_name: String,
+}
+
+impl From<RawThread> for NotificationThread {
+    fn from(thread: RawThread) -> Self {
+        // Subject URLs point at the API, e.g. `/repos/owner/repo/issues/42`
+        let number = thread
+            .subject
+            .url
+            .as_deref()
+            .and_then(|url| url.rsplit('/').next())
+            .and_then(|segment| segment.parse().ok())
+            .filter(|_| matches!(thread.subject.kind.as_str(), "Issue" | "PullRequest"));
+
+        NotificationThread {
+            id: thread.id,
+            repo: thread.repository.full_name,
+            reason: thread.reason,
+            unread: thread.unread,
+            updated_at: thread.updated_at,
+            last_read_at: thread.last_read_at,
+            subject: NotificationSubject {
+                title: thread.subject.title,
+                kind: thread.subject.kind,
+                number,
+                url: thread.subject.url,
+            },
+        }
+    }
+}
+
+fn poll_interval(headers: &HeaderMap) -> Option<i64> {
+    headers
+        .get("x-poll-interval")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse().ok())
+}
+
+async fn load_threads(
+    octocrab: &Octocrab,
+    mut page: Page<RawThread>,
+) -> Result<Vec<NotificationThread>, String> {
+    let mut threads = Vec::new();
+    loop {
+        threads.extend(page.take_items().into_iter().map(NotificationThread::from));
+        if threads.len() >= MAX_NOTIFICATIONS {
+            threads.truncate(MAX_NOTIFICATIONS);
+            return Ok(threads);
+        }
+
+        match with_retry(Resource::Core, || {
+            octocrab.get_page::<RawThread>(&page.next)
+        })
+        .await?
+        {
+            Some(next) => page = next,
+            None => return Ok(threads),
+        }
+    }
+}
+
+/// Lists notification threads, newest first. GitHub's poll interval is respected unless
+/// `force_refresh` is set, and unchanged polls are answered with 304 without using quota.
+#[command]
+pub async fn fetch_notifications(
+    app: AppHandle,
+    all: Option<bool>,
+    participating: Option<bool>,
+    force_refresh: Option<bool>,
+    cache: State<'_, NotificationsCache>,
+) -> Result<NotificationList, String> {
+    let login = get_username(app)?;
+    let route = format!(
+        "/notifications?all={}&participating={}&per_page=50",
+        all.unwrap_or(false),
+        participating.unwrap_or(false)
+    );
+
+    let cached = {
+        let cache_guard = cache.cache.lock().map_err(|e| e.to_string())?;
+        cache_guard.get(&login).cloned()
+    };
+    // A differently filtered listing can't answer this one
+    let cached = cached.filter(|cached| cached.route == route);
+    if let Some(cached) = &cached {
+        if !force_refresh.unwrap_or(false) && Utc::now() < cached.next_poll() {
+            return Ok(cached.to_list());
+        }
+    }
+
+    let octocrab = get_client()?;
+    let conditional_headers = cached
+        .as_ref()
+        .and_then(|cached| cached.validators.as_ref())
+        .map(|validators| validators.to_headers());
+    let response = match with_retry(Resource::Core, || async {
+        let response = octocrab
+            ._get_with_headers(route.as_str(), conditional_headers.clone())
+            .await?;
+        record_headers(response.headers());
+        if response.status() == StatusCode::NOT_MODIFIED {
+            return Ok(response);
+        }
+        octocrab::map_github_error(response).await
+    })
+    .await
+    {
+        Ok(response) => response,
+        Err(e) => {
+            println!("Error fetching notifications: {}", e);
+            return cached.map(|cached| cached.to_list()).ok_or(e);
+        }
+    };
+
+    let poll_interval = poll_interval(response.headers()).unwrap_or(DEFAULT_POLL_INTERVAL_SECS);
+    if response.status() == StatusCode::NOT_MODIFIED {
+        let Some(mut cached) = cached else {
+            return Err("Received 304 Not Modified without cached notifications".to_string());
+        };
+        cached.poll_interval = poll_interval;
+        cached.last_polled = Utc::now();
+        let list = cached.to_list();
+        cache
+            .cache
+            .lock()
+            .map_err(|e| e.to_string())?
+            .insert(login, cached);
+        return Ok(list);
+    }
+
+    let validators = Validators::from_headers(route.clone(), response.headers());
+    let page = Page::<RawThread>::from_response(response)
+        .await
+        .map_err(|e| e.to_string())?;
+    let threads = load_threads(&octocrab, page).await?;
+
+    let cached = CachedNotifications {
+        route,
+        threads,
+        validators,
+        poll_interval,
+        last_polled: Utc::now(),
+    };
+    let list = cached.to_list();
+    cache
+        .cache
+        .lock()
+        .map_err(|e| e.to_string())?
+        .insert(login, cached);
+
+    Ok(list)
+}
+
+/// Applies `update` to the cached threads matching `filter`.
+fn update_cached(
+    cache: &NotificationsCache,
+    login: &str,
+    filter: impl Fn(&NotificationThread) -> bool,
+    update: impl Fn(&mut NotificationThread),
+) -> Result<(), String> {
+    let mut cache_guard = cache.cache.lock().map_err(|e| e.to_string())?;
+    if let Some(cached) = cache_guard.get_mut(login) {
+        cached
+            .threads
+            .iter_mut()
+            .filter(|thread| filter(thread))
+            .for_each(update);
+    }
+    Ok(())
+}
+
+#[command]
+pub async fn mark_notification_read(
+    app: AppHandle,
+    thread_id: String,
+    cache: State<'_, NotificationsCache>,
+) -> Result<(), String> {
+    let login = get_username(app)?;
+    let octocrab = get_client()?;
+
+    let route = format!("/notifications/threads/{}", thread_id);
+    with_rate_limit(Resource::Core, || async {
+        let response = octocrab._patch(route.as_str(), None::<&()>).await?;
+        octocrab::map_github_error(response).await.map(drop)
+    })
+    .await?;
+
+    let now = Utc::now().to_rfc3339();
+    update_cached(
+        &cache,
+        &login,
+        |thread| thread.id == thread_id,
+        |thread| {
+            thread.unread = false;
+            thread.last_read_at = Some(now.clone());
+        },
+    )
+}
+
+/// Marks every notification in a repository as read.
+#[command]
+pub async fn mark_repo_notifications_read(
+    app: AppHandle,
+    owner: String,
+    repo: String,
+    cache: State<'_, NotificationsCache>,
+) -> Result<(), String> {
+    let login = get_username(app)?;
+    let octocrab = get_client()?;
+
+    let now = Utc::now().to_rfc3339();
+    let route = format!("/repos/{}/{}/notifications", owner, repo);
+    // GitHub may answer 202 and finish marking in the background, the local copy is updated anyway
+    with_rate_limit(Resource::Core, || async {
+        let response = octocrab
+            ._put(route.as_str(), Some(&json!({ "last_read_at": now })))
+            .await?;
+        octocrab::map_github_error(response).await.map(drop)
+    })
+    .await?;
+
+    let full_name = format!("{}/{}", owner, repo);
+    update_cached(
+        &cache,
+        &login,
+        |thread| thread.repo.eq_ignore_ascii_case(&full_name),
+        |thread| {
+            thread.unread = false;
+            thread.last_read_at = Some(now.clone());
+        },
+    )
+}
+
+/// Ignores a thread until the user comments or is mentioned again, and marks it read.
+#[command]
+pub async fn mute_notification_thread(
+    app: AppHandle,
+    thread_id: String,
+    cache: State<'_, NotificationsCache>,
+) -> Result<(), String> {
+    let octocrab = get_client()?;
+
+    let route = format!("/notifications/threads/{}/subscription", thread_id);
+    with_rate_limit(Resource::Core, || async {
+        let response = octocrab
+            ._put(route.as_str(), Some(&json!({ "ignored": true })))
+            .await?;
+        octocrab::map_github_error(response).await.map(drop)
+    })
+    .await?;
+
+    mark_notification_read(app, thread_id, cache).await
+}
+
+/// Drops the user's subscription to a thread and marks it read. Notifications keep coming
+/// while the user watches the repository.
+#[command]
+pub async fn unsubscribe_notification_thread(
+    app: AppHandle,
+    thread_id: String,
+    cache: State<'_, NotificationsCache>,
+) -> Result<(), String> {
+    let octocrab = get_client()?;
+
+    let route = format!("/notifications/threads/{}/subscription", thread_id);
+    with_rate_limit(Resource::Core, || async {
+        let response = octocrab._delete(route.as_str(), None::<&()>).await?;
+        octocrab::map_github_error(response).await.map(drop)
+    })
+    .await?;
+
+    mark_notification_read(app, thread_id, cache).await
+}
@@ -0,0 +1,377 @@
+use super::comments::load_comments;
+use super::github_client::get_client;
+use super::issues::{CommentData, IssueStateFilter, Validators};
+use super::rate_limit::{all_pages, record_headers, with_retry, Classify, Failure, Resource};
+use chrono::{DateTime, Duration, Utc};
+use http::StatusCode;
+use octocrab::models::pulls::{PullRequest, Review, ReviewState};
+use octocrab::models::IssueState;
+use octocrab::{FromResponse, Page};
+use serde::Serialize;
+use std::sync::Mutex;
+use std::{collections::HashMap, sync::Arc};
+use tauri::{command, State};
+
+const MAX_PULL_REQUESTS: usize = 1000;
+
+#[derive(Debug, Default)]
+pub struct PullRequestsCache {
+    cache: Arc<Mutex<HashMap<String, CachedPullRequests>>>,
+}
+
+impl PullRequestsCache {
+    pub fn get_cache(&self) -> &Arc<Mutex<HashMap<String, CachedPullRequests>>> {
+        &self.cache
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CachedPullRequests {
+    pub pulls: Vec<PullRequestData>,
+    pub last_updated: DateTime<Utc>,
+    pub loaded: IssueStateFilter,
+    pub validators: Option<Validators>,
+    // Stopped at MAX_PULL_REQUESTS with more left on GitHub
+    pub truncated: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PullRequestList {
+    pub pulls: Vec<PullRequestData>,
+    pub truncated: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PullRequestData {
+    pub number: i64,
+    pub title: String,
+    pub state: String,
+    pub draft: bool,
+    pub created_at: String,
+    pub updated_at: String,
+    pub merged_at: Option<String>,
+    pub body: Option<String>,
+    pub creator: String,
+    pub labels: Vec<String>,
+    pub assignees: Vec<String>,
+    pub head_ref: String,
+    pub base_ref: String,
+    pub requested_reviewers: Vec<String>,
+    // Only returned when fetching a single pull request
+    pub mergeable: Option<bool>,
+    pub mergeable_state: Option<String>,
+    pub review_decision: Option<String>,
+    pub additions: Option<u64>,
+    pub deletions: Option<u64>,
+    pub changed_files: Option<u64>,
+    pub commits: Option<u64>,
+    // Conversation comments, also only loaded with a single pull request
+    pub comments: Vec<CommentData>,
+}
+
+impl From<PullRequest> for PullRequestData {
+    fn from(pull: PullRequest) -> Self {
+        let state = if pull.merged_at.is_some() {
+            String::from("merged")
+        } else {
+            match pull.state {
+                Some(IssueState::Open) => String::from("open"),
+                Some(IssueState::Closed) => String::from("closed"),
+                _ => String::from("unknown"),
+            }
+        };
+
+        PullRequestData {
+            number: pull.number as i64,
+            title: pull.title.unwrap_or_default(),
+            state,
+            draft: pull.draft.unwrap_or(false),
+            created_at: pull.created_at.map(|t| t.to_rfc3339()).unwrap_or_default(),
+            updated_at: pull.updated_at.map(|t| t.to_rfc3339()).unwrap_or_default(),
+            merged_at: pull.merged_at.map(|t| t.to_rfc3339()),
+            body: pull.body,
+            creator: pull.user.map(|user| user.login).unwrap_or_default(),
+            labels: pull
+                .labels
+                .unwrap_or_default()
+                .into_iter()
+                .map(|label| label.name)
+                .collect(),
+            assignees: pull
+                .assignees
+                .unwrap_or_default()
+                .into_iter()
+                .map(|user| user.login)
+                .collect(),
+            head_ref: pull.head.ref_field,
+            base_ref: pull.base.ref_field,
+            requested_reviewers: pull
+                .requested_reviewers
+                .unwrap_or_default()
+                .into_iter()
+                .map(|user| user.login)
+                .collect(),
+            mergeable: pull.mergeable,
+            mergeable_state: pull
+                .mergeable_state
+                .and_then(|state| serde_json::to_value(state).ok())
+                .and_then(|value| value.as_str().map(String::from)),
+            review_decision: None,
+            additions: pull.additions,
+            deletions: pull.deletions,
+            changed_files: pull.changed_files,
+            commits: pull.commits,
+            comments: Vec::new(),
+        }
+    }
+}
+
+/// Works out the overall review state the way GitHub shows it: any outstanding
+/// "changes requested" wins, then approvals, otherwise a review is still required.
+fn review_decision(reviews: &[Review], requested_reviewers: &[String]) -> Option<String> {
+    let mut latest: HashMap<String, &ReviewState> = HashMap::new();
+    for review in reviews {
+        if let (Some(user), Some(state)) = (&review.user, &review.state) {
+            if matches!(
+                state,
+                ReviewState::Approved | ReviewState::ChangesRequested | ReviewState::Dismissed
+            ) {
+                latest.insert(user.login.clone(), state);
+            }
+        }
+    }
+
+    if latest
+        .values()
+        .any(|state| matches!(state, ReviewState::ChangesRequested))
+    {
+        Some(String::from("changes_requested"))
+    } else if requested_reviewers.is_empty()
+        && latest
+            .values()
+            .any(|state| matches!(state, ReviewState::Approved))
+    {
+        Some(String::from("approved"))
+    } else if !requested_reviewers.is_empty() {
+        Some(String::from("review_required"))
+    } else {
+        None
+    }
+}
+
+#[command]
+pub async fn fetch_pull_requests(
+    owner: String,
+    repo: String,
+    cache: State<'_, PullRequestsCache>,
+    force_refresh: bool,
+    state: Option<IssueStateFilter>,
+) -> Result<PullRequestList, String> {
+    println!("Fetching pull requests for {}/{}", owner, repo);
+
+    let cache_key = format!("{}/{}", owner, repo);
+    let state = state.unwrap_or_default();
+    let route = format!(
+        "/repos/{}/{}/pulls?state={}&sort=updated&direction=desc&per_page=100",
+        owner,
+        repo,
+        state.as_str()
+    );
+
+    // Check cache
+    let mut conditional_headers = None;
+    {
+        let cache_guard = cache.cache.lock().map_err(|e| e.to_string())?;
+        if let Some(cached) = cache_guard.get(&cache_key) {
+            if cached.loaded == state {
+                if !force_refresh && Utc::now() - cached.last_updated < Duration::minutes(5) {
+                    println!("Returning cached pull requests");
+                    return Ok(PullRequestList {
+                        pulls: cached.pulls.clone(),
+                        truncated: cached.truncated,
+                    });
+                }
+
+                conditional_headers = cached
+                    .validators
+                    .as_ref()
+                    .filter(|validators| validators.route == route)
+                    .map(|validators| validators.to_headers());
+            }
+        }
+    }
+
+    let octocrab = get_client()?;
+    let response = match with_retry(Resource::Core, || async {
+        let response = match octocrab
+            ._get_with_headers(route.as_str(), conditional_headers.clone())
+            .await
+        {
+            Err(e) if e.classify() == Failure::Fatal => return Ok(Err(e)),
+            result => result?,
+        };
+        record_headers(response.headers());
+        if response.status() == StatusCode::NOT_MODIFIED {
+            return Ok(Ok(response));
+        }
+        match octocrab::map_github_error(response).await {
+            Err(e) if e.classify() == Failure::Fatal => Ok(Err(e)),
+            result => result.map(Ok),
+        }
+    })
+    .await
+    {
+        Ok(Ok(response)) => response,
+        Ok(Err(e)) => return Err(e.to_string()),
+        Err(e) => {
+            println!("Error fetching pull requests: {}", e);
+            // Serve whatever we have when GitHub can't be reached
+            return stored_pull_requests(&cache, &cache_key, state, e);
+        }
+    };
+
+    if response.status() == StatusCode::NOT_MODIFIED {
+        println!("Pull requests unchanged since last fetch");
+        let mut cache_guard = cache.cache.lock().map_err(|e| e.to_string())?;
+        if let Some(cached) = cache_guard.get_mut(&cache_key) {
+            cached.last_updated = Utc::now();
+            return Ok(PullRequestList {
+                pulls: cached.pulls.clone(),
+                truncated: cached.truncated,
+            });
+        }
+        return Err("Received 304 Not Modified without cached pull requests".to_string());
+    }
+
+    let validators = Validators::from_headers(route, response.headers());
+    let mut page = Page::<PullRequest>::from_response(response)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let mut fetched: Vec<PullRequest> = Vec::new();
+    let mut truncated = false;
+    loop {
+        fetched.extend(page.take_items());
+
+        if fetched.len() >= MAX_PULL_REQUESTS {
+            truncated = fetched.len() > MAX_PULL_REQUESTS || page.next.is_some();
+            if truncated {
+                println!(
+                    "Stopped after {} pull requests for {}/{}, more are available",
+                    MAX_PULL_REQUESTS, owner, repo
+                );
+            }
+            fetched.truncate(MAX_PULL_REQUESTS);
+            break;
+        }
+
+        match with_retry(Resource::Core, || {
+            octocrab.get_page::<PullRequest>(&page.next)
+        })
+        .await?
+        {
+            Some(next_page) => page = next_page,
+            None => break,
+        }
+    }
+
+    // Keep details already loaded for pull requests that haven't changed
+    let mut cache_guard = cache.cache.lock().map_err(|e| e.to_string())?;
+    let previous: HashMap<i64, PullRequestData> = cache_guard
+        .remove(&cache_key)
+        .map(|cached| {
+            cached
+                .pulls
+                .into_iter()
+                .map(|pull| (pull.number, pull))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let pulls: Vec<PullRequestData> = fetched
+        .into_iter()
+        .map(PullRequestData::from)
+        .map(|pull| match previous.get(&pull.number) {
+            Some(known) if known.updated_at == pull.updated_at => known.clone(),
+            _ => pull,
+        })
+        .collect();
+
+    cache_guard.insert(
+        cache_key,
+        CachedPullRequests {
+            pulls: pulls.clone(),
+            last_updated: Utc::now(),
+            loaded: state,
+            validators,
+            truncated,
+        },
+    );
+
+    println!("Returning {} pull requests", pulls.len());
+    Ok(PullRequestList { pulls, truncated })
+}
+
+fn stored_pull_requests(
+    cache: &PullRequestsCache,
+    cache_key: &str,
+    state: IssueStateFilter,
+    error: String,
+) -> Result<PullRequestList, String> {
+    let cache_guard = cache.cache.lock().map_err(|e| e.to_string())?;
+    match cache_guard.get(cache_key) {
+        Some(cached) if cached.loaded == state => {
+            println!(
+                "Returning stored pull requests from {}",
+                cached.last_updated
+            );
+            Ok(PullRequestList {
+                pulls: cached.pulls.clone(),
+                truncated: cached.truncated,
+            })
+        }
+        _ => Err(error),
+    }
+}
+
+#[command]
+pub async fn fetch_pull_request(
+    owner: String,
+    repo: String,
+    pr_number: i64,
+    cache: State<'_, PullRequestsCache>,
+) -> Result<PullRequestData, String> {
+    let octocrab = get_client()?;
+
+    let pull = with_retry(Resource::Core, || async {
+        octocrab.pulls(&owner, &repo).get(pr_number as u64).await
+    })
+    .await?;
+
+    let reviews = with_retry(Resource::Core, || async {
+        octocrab
+            .pulls(&owner, &repo)
+            .list_reviews(pr_number as u64)
+            .per_page(100)
+            .send()
+            .await
+    })
+    .await?;
+    let reviews = all_pages(&octocrab, reviews).await?;
+
+    let mut pull_data = PullRequestData::from(pull);
+    pull_data.review_decision = review_decision(&reviews, &pull_data.requested_reviewers);
+    pull_data.comments = load_comments(&octocrab, &owner, &repo, pr_number).await?;
+
+    // Update cache with the detailed pull request
+    let cache_key = format!("{}/{}", owner, repo);
+    let mut cache_guard = cache.cache.lock().map_err(|e| e.to_string())?;
+    if let Some(cached) = cache_guard.get_mut(&cache_key) {
+        match cached.pulls.iter_mut().find(|p| p.number == pr_number) {
+            Some(existing) => *existing = pull_data.clone(),
+            None => cached.pulls.push(pull_data.clone()),
+        }
+    }
+
+    Ok(pull_data)
+}
@@ -0,0 +1,436 @@
+use super::github_client::get_client;
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use http::HeaderMap;
+use octocrab::{Octocrab, Page};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::future::Future;
+use std::sync::{Mutex, OnceLock};
+use tauri::command;
+
+/// Attempts made for reads before giving up on a transient failure.
+const MAX_ATTEMPTS: u32 = 4;
+const BASE_BACKOFF_MS: i64 = 500;
+/// Longer waits than this fail straight away instead of blocking the command.
+const MAX_RATE_LIMIT_WAIT_SECS: i64 = 90;
+/// GitHub asks for at least a minute between retries after a secondary rate limit.
+const SECONDARY_LIMIT_WAIT_SECS: i64 = 60;
+/// Below this share of the quota the UI should warn before starting a big sync.
+const LOW_QUOTA_RATIO: f64 = 0.1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Resource {
+    Core,
+    Search,
+    Graphql,
+}
+
+impl Resource {
+    fn as_str(self) -> &'static str {
+        match self {
+            Resource::Core => "core",
+            Resource::Search => "search",
+            Resource::Graphql => "graphql",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Quota {
+    limit: i64,
+    remaining: i64,
+    used: i64,
+    reset: DateTime<Utc>,
+    observed_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Default)]
+struct Tracker {
+    quotas: HashMap<String, Quota>,
+    // Set by `Retry-After` or a secondary rate limit, applies to every resource
+    blocked_until: Option<DateTime<Utc>>,
+    // Calls since the quotas were last read whose headers octocrab didn't hand back
+    untracked: u32,
+}
+
+fn tracker() -> &'static Mutex<Tracker> {
+    static TRACKER: OnceLock<Mutex<Tracker>> = OnceLock::new();
+    TRACKER.get_or_init(Mutex::default)
+}
+
+/// How a failed call should be handled.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Failure {
+    RateLimited { secondary: bool },
+    Transient,
+    Fatal,
+}
+
+pub trait Classify {
+    fn classify(&self) -> Failure;
+}
+
+impl Classify for octocrab::Error {
+    fn classify(&self) -> Failure {
+        match self {
+            octocrab::Error::GitHub { source, .. } => {
+                classify_status(source.status_code.as_u16(), &source.message)
+            }
+            octocrab::Error::Hyper { .. } | octocrab::Error::Service { .. } => Failure::Transient,
+            _ => Failure::Fatal,
+        }
+    }
+}
+
+pub fn classify_status(status: u16, message: &str) -> Failure {
+    let message = message.to_lowercase();
+    if status == 429 || (status == 403 && message.contains("rate limit")) {
+        Failure::RateLimited {
+            secondary: message.contains("secondary"),
+        }
+    } else if status >= 500 {
+        Failure::Transient
+    } else {
+        Failure::Fatal
+    }
+}
+
+/// Records rate limit headers from a raw octocrab response.
+pub fn record_headers(headers: &HeaderMap) {
+    record_with(|name| {
+        headers
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .map(String::from)
+    });
+}
+
+/// Records `x-ratelimit-*` and `retry-after` headers, read through `header`.
+pub fn record_with(header: impl Fn(&str) -> Option<String>) {
+    let number = |name: &str| header(name).and_then(|value| value.trim().parse::<i64>().ok());
+    let Ok(mut tracker) = tracker().lock() else {
+        return;
+    };
+
+    if let Some(seconds) = number("retry-after") {
+        tracker.blocked_until = Some(Utc::now() + Duration::seconds(seconds));
+    }
+
+    let (Some(limit), Some(remaining), Some(reset)) = (
+        number("x-ratelimit-limit"),
+        number("x-ratelimit-remaining"),
+        number("x-ratelimit-reset"),
+    ) else {
+        return;
+    };
+    let resource = header("x-ratelimit-resource").unwrap_or_else(|| "core".to_string());
+
+    tracker.quotas.insert(
+        resource,
+        Quota {
+            limit,
+            remaining,
+            used: number("x-ratelimit-used").unwrap_or(limit - remaining),
+            reset: Utc
+                .timestamp_opt(reset, 0)
+                .single()
+                .unwrap_or_else(Utc::now),
+            observed_at: Utc::now(),
+        },
+    );
+}
+
+/// Typed octocrab calls don't expose their response headers. When a call left no
+/// reading of its quota, count it against the last one until `/rate_limit` is read again.
+fn note_call(resource: Resource, started: DateTime<Utc>) {
+    let Ok(mut guard) = tracker().lock() else {
+        return;
+    };
+    let tracker = &mut *guard;
+
+    if let Some(quota) = tracker.quotas.get_mut(resource.as_str()) {
+        if quota.observed_at >= started {
+            return;
+        }
+        if quota.reset > Utc::now() {
+            quota.remaining = (quota.remaining - 1).max(0);
+            quota.used += 1;
+        }
+    }
+    tracker.untracked += 1;
+}
+
+/// How long to hold off before calling `resource`, if its quota is used up.
+fn quota_wait(resource: Resource) -> Option<Duration> {
+    let tracker = tracker().lock().ok()?;
+    let now = Utc::now();
+
+    let blocked = tracker.blocked_until.filter(|until| *until > now);
+    let exhausted = tracker
+        .quotas
+        .get(resource.as_str())
+        .filter(|quota| quota.remaining == 0 && quota.reset > now)
+        .map(|quota| quota.reset);
+
+    blocked.max(exhausted).map(|until| until - now)
+}
+
+fn rate_limit_message(resource: Resource, wait: Duration) -> String {
+    format!(
+        "GitHub rate limit exceeded for {} requests, try again at {}",
+        resource.as_str(),
+        (Utc::now() + wait)
+            .with_timezone(&chrono::Local)
+            .format("%H:%M")
+    )
+}
+
+fn backoff(attempt: u32) -> Duration {
+    // A little jitter so parallel requests don't retry in lockstep
+    let jitter = Utc::now().timestamp_subsec_millis() as i64 % 250;
+    Duration::milliseconds(BASE_BACKOFF_MS * 2_i64.pow(attempt) + jitter)
+}
+
+async fn sleep(wait: Duration) {
+    tokio::time::sleep(wait.to_std().unwrap_or_default()).await;
+}
+
+async fn run<T, E, F, Fut>(resource: Resource, idempotent: bool, call: F) -> Result<T, String>
+where
+    E: Classify + Display,
+    F: Fn() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+{
+    let mut attempt = 0;
+    loop {
+        if let Some(wait) = quota_wait(resource) {
+            if wait > Duration::seconds(MAX_RATE_LIMIT_WAIT_SECS) {
+                return Err(rate_limit_message(resource, wait));
+            }
+            println!(
+                "Waiting {}s for the {} rate limit",
+                wait.num_seconds(),
+                resource.as_str()
+            );
+            sleep(wait).await;
+        }
+
+        let started = Utc::now();
+        let result = call().await;
+        note_call(resource, started);
+        let error = match result {
+            Ok(value) => return Ok(value),
+            Err(error) => error,
+        };
+        attempt += 1;
+
+        let wait = match error.classify() {
+            Failure::RateLimited { secondary } => {
+                // A typed call's error doesn't carry the reset time, so look it up
+                if !secondary && quota_wait(resource).is_none() {
+                    if let Err(e) = load_quotas().await {
+                        println!("Failed to read rate limits: {}", e);
+                    }
+                }
+                let wait = quota_wait(resource).unwrap_or_else(|| {
+                    if secondary {
+                        Duration::seconds(SECONDARY_LIMIT_WAIT_SECS * 2_i64.pow(attempt - 1))
+                    } else {
+                        Duration::seconds(SECONDARY_LIMIT_WAIT_SECS)
+                    }
+                });
+                if wait > Duration::seconds(MAX_RATE_LIMIT_WAIT_SECS) {
+                    return Err(rate_limit_message(resource, wait));
+                }
+                wait
+            }
+            // Writes may already have been applied, so only rate limit rejections are retried
+            Failure::Transient if idempotent => backoff(attempt),
+            _ => return Err(error.to_string()),
+        };
+
+        if attempt >= MAX_ATTEMPTS {
+            return Err(error.to_string());
+        }
+        println!(
+            "GitHub request failed ({}), retrying in {}ms",
+            error,
+            wait.num_milliseconds()
+        );
+        sleep(wait).await;
+    }
+}
+
+/// Runs a read, waiting out rate limits and retrying server and network errors with backoff.
+pub async fn with_retry<T, E, F, Fut>(resource: Resource, call: F) -> Result<T, String>
+where
+    E: Classify + Display,
+    F: Fn() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+{
+    run(resource, true, call).await
+}
+
+/// Collects every page after `page`, retrying each request like `with_retry`.
+pub async fn all_pages<T: DeserializeOwned>(
+    octocrab: &Octocrab,
+    mut page: Page<T>,
+) -> Result<Vec<T>, String> {
+    let mut items = page.take_items();
+    loop {
+        let next = with_retry(Resource::Core, || octocrab.get_page::<T>(&page.next)).await?;
+        match next {
+            Some(mut next) => {
+                items.extend(next.take_items());
+                page = next;
+            }
+            None => return Ok(items),
+        }
+    }
+}
+
+/// Runs a write, retrying only when GitHub rejected it for rate limiting.
+pub async fn with_rate_limit<T, E, F, Fut>(resource: Resource, call: F) -> Result<T, String>
+where
+    E: Classify + Display,
+    F: Fn() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+{
+    run(resource, false, call).await
+}
+
+#[derive(Debug, Serialize)]
+pub struct QuotaStatus {
+    resource: String,
+    limit: i64,
+    remaining: i64,
+    used: i64,
+    reset_at: String,
+    observed_at: String,
+    low: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RateLimitStatus {
+    resources: Vec<QuotaStatus>,
+    blocked_until: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RateLimitResponse {
+    resources: HashMap<String, RateLimitResource>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RateLimitResource {
+    limit: i64,
+    remaining: i64,
+    #[serde(default)]
+    used: i64,
+    reset: i64,
+}
+
+async fn fetch_quotas(octocrab: &Octocrab) -> octocrab::Result<RateLimitResponse> {
+    octocrab.get("/rate_limit", None::<&()>).await
+}
+
+/// Reads every quota once, without retrying, for use while a call is being retried.
+async fn load_quotas() -> Result<(), String> {
+    let response = fetch_quotas(&get_client()?)
+        .await
+        .map_err(|e| e.to_string())?;
+    store_quotas(response)
+}
+
+/// Asks GitHub for every quota. The `/rate_limit` endpoint doesn't count against them.
+async fn refresh_quotas() -> Result<(), String> {
+    let octocrab = get_client()?;
+    let response = with_retry(Resource::Core, || fetch_quotas(&octocrab)).await?;
+    store_quotas(response)
+}
+
+/// Re-reads the quotas if calls were made since that couldn't record them. Meant for
+/// the end of a batch of typed octocrab calls.
+pub async fn refresh_untracked_quotas() {
+    let untracked = tracker().lock().is_ok_and(|tracker| tracker.untracked > 0);
+    if untracked {
+        if let Err(e) = refresh_quotas().await {
+            println!("Failed to refresh rate limits: {}", e);
+        }
+    }
+}
+
+fn store_quotas(response: RateLimitResponse) -> Result<(), String> {
+    let mut tracker = tracker().lock().map_err(|e| e.to_string())?;
+    tracker.untracked = 0;
+    for (resource, quota) in response.resources {
+        tracker.quotas.insert(
+            resource,
+            Quota {
+                limit: quota.limit,
+                remaining: quota.remaining,
+                used: quota.used,
+                reset: Utc
+                    .timestamp_opt(quota.reset, 0)
+                    .single()
+                    .unwrap_or_else(Utc::now),
+                observed_at: Utc::now(),
+            },
+        );
+    }
+
+    Ok(())
+}
+
+#[command]
+pub async fn get_rate_limit_status(refresh: Option<bool>) -> Result<RateLimitStatus, String> {
+    let stale = {
+        let tracker = tracker().lock().map_err(|e| e.to_string())?;
+        let now = Utc::now();
+        tracker.untracked > 0
+            || ["core", "search", "graphql"].iter().any(|resource| {
+                tracker.quotas.get(*resource).is_none_or(|quota| {
+                    now - quota.observed_at > Duration::minutes(1) || quota.reset < now
+                })
+            })
+    };
+    if refresh.unwrap_or(false) || stale {
+        refresh_quotas().await?;
+    }
+
+    let tracker = tracker().lock().map_err(|e| e.to_string())?;
+    let now = Utc::now();
+    let mut resources: Vec<QuotaStatus> = tracker
+        .quotas
+        .iter()
+        .map(|(resource, quota)| {
+            // A quota past its reset time has been refilled
+            let (remaining, used) = if quota.reset < now {
+                (quota.limit, 0)
+            } else {
+                (quota.remaining, quota.used)
+            };
+            QuotaStatus {
+                resource: resource.clone(),
+                limit: quota.limit,
+                remaining,
+                used,
+                reset_at: quota.reset.to_rfc3339(),
+                observed_at: quota.observed_at.to_rfc3339(),
+                low: (remaining as f64) < quota.limit as f64 * LOW_QUOTA_RATIO,
+            }
+        })
+        .collect();
+    resources.sort_by(|a, b| a.resource.cmp(&b.resource));
+
+    Ok(RateLimitStatus {
+        resources,
+        blocked_until: tracker
+            .blocked_until
+            .filter(|until| *until > now)
+            .map(|until| until.to_rfc3339()),
+    })
+}
@@ -0,0 +1,206 @@
+use super::github_client::get_client;
+use super::interactions::fetch_single_issue;
+use super::issues::{IssueData, IssuesCache};
+use super::oauth::get_username;
+use super::rate_limit::{all_pages, with_rate_limit, with_retry, Resource};
+use octocrab::{Octocrab, Page};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tauri::{command, AppHandle, State};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ReactionContent {
+    #[serde(rename = "+1")]
+    PlusOne,
+    #[serde(rename = "-1")]
+    MinusOne,
+    #[serde(rename = "laugh")]
+    Laugh,
+    #[serde(rename = "hooray")]
+    Hooray,
+    #[serde(rename = "confused")]
+    Confused,
+    #[serde(rename = "heart")]
+    Heart,
+    #[serde(rename = "rocket")]
+    Rocket,
+    #[serde(rename = "eyes")]
+    Eyes,
+}
+
+#[derive(Debug, Deserialize)]
+struct Reaction {
+    id: u64,
+    content: ReactionContent,
+    user: Option<ReactionUser>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ReactionUser {
+    login: String,
+}
+
+/// What a reaction is attached to.
+enum Subject {
+    Issue(i64),
+    Comment(i64),
+}
+
+impl Subject {
+    fn route(&self, owner: &str, repo: &str) -> String {
+        match self {
+            Subject::Issue(number) => {
+                format!("/repos/{}/{}/issues/{}/reactions", owner, repo, number)
+            }
+            Subject::Comment(id) => {
+                format!("/repos/{}/{}/issues/comments/{}/reactions", owner, repo, id)
+            }
+        }
+    }
+}
+
+async fn add_reaction(
+    octocrab: &Octocrab,
+    owner: &str,
+    repo: &str,
+    subject: Subject,
+    content: ReactionContent,
+) -> Result<(), String> {
+    let route = subject.route(owner, repo);
+    // GitHub answers 200 instead of 201 when the reaction already exists, so adding is idempotent
+    let _: Reaction = with_rate_limit(Resource::Core, || {
+        octocrab.post(&route, Some(&json!({ "content": content })))
+    })
+    .await?;
+
+    Ok(())
+}
+
+async fn remove_reaction(
+    octocrab: &Octocrab,
+    owner: &str,
+    repo: &str,
+    subject: Subject,
+    content: ReactionContent,
+    login: &str,
+) -> Result<(), String> {
+    let route = subject.route(owner, repo);
+    // Filtering by content server-side keeps busy issues to a page or two
+    let query = json!({ "content": content, "per_page": 100 });
+    let page: Page<Reaction> =
+        with_retry(Resource::Core, || octocrab.get(&route, Some(&query))).await?;
+    let reactions = all_pages(octocrab, page).await?;
+
+    // The endpoint only deletes by id, so find the user's own reaction first
+    let Some(reaction) = reactions.into_iter().find(|reaction| {
+        reaction.content == content
+            && reaction
+                .user
+                .as_ref()
+                .is_some_and(|user| user.login.eq_ignore_ascii_case(login))
+    }) else {
+        return Ok(());
+    };
+
+    let route = format!("{}/{}", route, reaction.id);
+    with_rate_limit(Resource::Core, || async {
+        let response = octocrab._delete(route.as_str(), None::<&()>).await?;
+        octocrab::map_github_error(response).await.map(drop)
+    })
+    .await
+}
+
+#[command]
+pub async fn add_issue_reaction(
+    app: AppHandle,
+    owner: String,
+    repo: String,
+    issue_number: i64,
+    content: ReactionContent,
+    cache: State<'_, IssuesCache>,
+) -> Result<IssueData, String> {
+    let octocrab = get_client()?;
+    add_reaction(
+        &octocrab,
+        &owner,
+        &repo,
+        Subject::Issue(issue_number),
+        content,
+    )
+    .await?;
+
+    fetch_single_issue(app, owner, repo, issue_number, cache).await
+}
+
+#[command]
+pub async fn remove_issue_reaction(
+    app: AppHandle,
+    owner: String,
+    repo: String,
+    issue_number: i64,
+    content: ReactionContent,
+    cache: State<'_, IssuesCache>,
+) -> Result<IssueData, String> {
+    let octocrab = get_client()?;
+    let login = get_username(app.clone())?;
+    remove_reaction(
+        &octocrab,
+        &owner,
+        &repo,
+        Subject::Issue(issue_number),
+        content,
+        &login,
+    )
+    .await?;
+
+    fetch_single_issue(app, owner, repo, issue_number, cache).await
+}
+
+/// `issue_number` is the issue the comment belongs to, reloaded into the cache afterwards.
+#[command]
+pub async fn add_comment_reaction(
+    app: AppHandle,
+    owner: String,
+    repo: String,
+    issue_number: i64,
+    comment_id: i64,
+    content: ReactionContent,
+    cache: State<'_, IssuesCache>,
+) -> Result<IssueData, String> {
+    let octocrab = get_client()?;
+    add_reaction(
+        &octocrab,
+        &owner,
+        &repo,
+        Subject::Comment(comment_id),
+        content,
+    )
+    .await?;
+
+    fetch_single_issue(app, owner, repo, issue_number, cache).await
+}
+
+#[command]
+pub async fn remove_comment_reaction(
+    app: AppHandle,
+    owner: String,
+    repo: String,
+    issue_number: i64,
+    comment_id: i64,
+    content: ReactionContent,
+    cache: State<'_, IssuesCache>,
+) -> Result<IssueData, String> {
+    let octocrab = get_client()?;
+    let login = get_username(app.clone())?;
+    remove_reaction(
+        &octocrab,
+        &owner,
+        &repo,
+        Subject::Comment(comment_id),
+        content,
+        &login,
+    )
+    .await?;
+
+    fetch_single_issue(app, owner, repo, issue_number, cache).await
+}
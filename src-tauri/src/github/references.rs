@@ -0,0 +1,324 @@
+use serde::Serialize;
+
+const GITHUB_URL: &str = "github.com/";
+const CLOSING_KEYWORDS: &[&str] = &[
+    "close", "closes", "closed", "fix", "fixes", "fixed", "resolve", "resolves", "resolved",
+];
+const DEPENDENCY_PHRASES: &[&str] = &["blocked by", "depends on", "depend on"];
+
+/// A mention of an issue in markdown: `#12`, `owner/repo#12` or a link to the issue or PR.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
+pub struct IssueRef {
+    /// `owner/repo`, `None` for an issue in the same repo.
+    pub repo: Option<String>,
+    pub number: i64,
+}
+
+fn is_word(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || byte == b'_'
+}
+
+fn is_owner_char(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || byte == b'-'
+}
+
+fn is_repo_char(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'_' | b'.')
+}
+
+/// Reads `owner/repo` backwards from `end`, the position of the `#`. Returns the name and
+/// where it starts.
+fn repo_before(bytes: &[u8], end: usize) -> Option<(usize, String)> {
+    let mut start = end;
+    while start > 0 && is_repo_char(bytes[start - 1]) {
+        start -= 1;
+    }
+    if start == end || start == 0 || bytes[start - 1] != b'/' {
+        return None;
+    }
+
+    let slash = start - 1;
+    let mut owner_start = slash;
+    while owner_start > 0 && is_owner_char(bytes[owner_start - 1]) {
+        owner_start -= 1;
+    }
+    if owner_start == slash {
+        return None;
+    }
+    // Part of a longer path, like a URL
+    if owner_start > 0 && (is_word(bytes[owner_start - 1]) || bytes[owner_start - 1] == b'/') {
+        return None;
+    }
+
+    let name = std::str::from_utf8(&bytes[owner_start..end]).ok()?;
+    Some((owner_start, name.to_string()))
+}
+
+/// How an issue or PR points at another.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReferenceKind {
+    Mentions,
+    /// `fixes #12`, the target is closed when the source is merged.
+    Closes,
+    /// `blocked by #12` or `depends on #12`, the target has to be done first.
+    DependsOn,
+    /// The target is an item of the source's task list.
+    Tracks,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Reference {
+    pub issue: IssueRef,
+    pub kind: ReferenceKind,
+}
+
+/// `#12` and `owner/repo#12`, with the position each starts at.
+fn shorthand_references(text: &str) -> Vec<(usize, IssueRef)> {
+    let bytes = text.as_bytes();
+    let mut found = Vec::new();
+
+    for (hash, _) in text.match_indices('#') {
+        let digits = bytes[hash + 1..]
+            .iter()
+            .take_while(|byte| byte.is_ascii_digit())
+            .count();
+        let end = hash + 1 + digits;
+        if digits == 0 || (end < bytes.len() && is_word(bytes[end])) {
+            continue;
+        }
+        let Ok(number) = text[hash + 1..end].parse::<i64>() else {
+            continue;
+        };
+
+        let (start, repo) = match hash.checked_sub(1).map(|i| bytes[i]) {
+            // HTML entities like `&#123;`
+            Some(b'&') => continue,
+            Some(byte) if is_repo_char(byte) => match repo_before(bytes, hash) {
+                Some((start, repo)) => (start, Some(repo)),
+                None => continue,
+            },
+            _ => (hash, None),
+        };
+        found.push((start, IssueRef { repo, number }));
+    }
+
+    found
+}
+
+/// Links like `https://github.com/owner/repo/issues/12` or `.../pull/12`, with the
+/// position each starts at.
+fn url_references(text: &str) -> Vec<(usize, IssueRef)> {
+    let mut found = Vec::new();
+
+    for (at, _) in text.match_indices(GITHUB_URL) {
+        let prefix = &text[..at];
+        let start = ["https://www.", "http://www.", "https://", "http://", "www."]
+            .iter()
+            .find(|scheme| prefix.ends_with(*scheme))
+            .map_or(at, |scheme| at - scheme.len());
+        // Other hosts, like `api.github.com/`
+        if let Some(&before) = start.checked_sub(1).and_then(|i| text.as_bytes().get(i)) {
+            if is_word(before) || matches!(before, b'.' | b'/' | b'-') {
+                continue;
+            }
+        }
+
+        let mut parts = text[at + GITHUB_URL.len()..].splitn(4, '/');
+        let (Some(owner), Some(repo), Some(kind), Some(rest)) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            continue;
+        };
+        if owner.is_empty()
+            || repo.is_empty()
+            || !owner.bytes().all(is_owner_char)
+            || !repo.bytes().all(is_repo_char)
+            || !matches!(kind, "issues" | "pull")
+        {
+            continue;
+        }
+
+        let digits = rest.bytes().take_while(u8::is_ascii_digit).count();
+        if digits == 0
+            || rest
+                .as_bytes()
+                .get(digits)
+                .is_some_and(|byte| is_word(*byte))
+        {
+            continue;
+        }
+        let Ok(number) = rest[..digits].parse::<i64>() else {
+            continue;
+        };
+
+        found.push((
+            start,
+            IssueRef {
+                repo: Some(format!("{}/{}", owner, repo)),
+                number,
+            },
+        ));
+    }
+
+    found
+}
+
+fn scan(text: &str) -> Vec<(usize, IssueRef)> {
+    let mut found = shorthand_references(text);
+    found.extend(url_references(text));
+    found.sort_by_key(|(start, _)| *start);
+    found
+}
+
+/// Whether `text` ends with `phrase` as whole words.
+fn ends_with_words(text: &str, phrase: &str) -> bool {
+    text.ends_with(phrase)
+        && text[..text.len() - phrase.len()]
+            .chars()
+            .next_back()
+            .is_none_or(|ch| !ch.is_alphanumeric())
+}
+
+/// The kind of reference implied by the words just before it, like `fixes` or `blocked by`.
+fn kind_before(text: &str, start: usize) -> ReferenceKind {
+    let before = text[..start].trim_end_matches([' ', '\t', ':']);
+    let mut tail_start = before.len().saturating_sub(16);
+    while !before.is_char_boundary(tail_start) {
+        tail_start -= 1;
+    }
+    let tail = before[tail_start..].to_lowercase();
+
+    if CLOSING_KEYWORDS
+        .iter()
+        .any(|keyword| ends_with_words(&tail, keyword))
+    {
+        ReferenceKind::Closes
+    } else if DEPENDENCY_PHRASES
+        .iter()
+        .any(|phrase| ends_with_words(&tail, phrase))
+    {
+        ReferenceKind::DependsOn
+    } else {
+        ReferenceKind::Mentions
+    }
+}
+
+/// Finds the issue references in `text`, in order and without duplicates.
+pub fn find_references(text: &str) -> Vec<IssueRef> {
+    let mut references: Vec<IssueRef> = Vec::new();
+    for (_, reference) in scan(text) {
+        if !references.contains(&reference) {
+            references.push(reference);
+        }
+    }
+    references
+}
+
+/// Finds the issue references in `text` along with how each is meant. An issue referenced
+/// more than once keeps the most specific kind.
+pub fn parse_references(text: &str) -> Vec<Reference> {
+    let mut references: Vec<Reference> = Vec::new();
+    for (start, issue) in scan(text) {
+        let kind = kind_before(text, start);
+        match references.iter_mut().find(|r| r.issue == issue) {
+            Some(existing) if existing.kind == ReferenceKind::Mentions => existing.kind = kind,
+            Some(_) => {}
+            None => references.push(Reference { issue, kind }),
+        }
+    }
+    references
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ReferenceKind::*;
+
+    fn local(number: i64) -> IssueRef {
+        IssueRef { repo: None, number }
+    }
+
+    fn other(repo: &str, number: i64) -> IssueRef {
+        IssueRef {
+            repo: Some(repo.to_string()),
+            number,
+        }
+    }
+
+    fn kinds(text: &str) -> Vec<(i64, ReferenceKind)> {
+        parse_references(text)
+            .into_iter()
+            .map(|reference| (reference.issue.number, reference.kind))
+            .collect()
+    }
+
+    #[test]
+    fn finds_shorthand_references_in_order_without_duplicates() {
+        assert_eq!(
+            find_references("See #12, (#4) and owner/repo#3. Also #12"),
+            vec![local(12), local(4), other("owner/repo", 3)]
+        );
+        assert_eq!(
+            find_references("my-org/my.repo#99"),
+            vec![other("my-org/my.repo", 99)]
+        );
+    }
+
+    #[test]
+    fn ignores_entities_and_words_that_contain_a_hash() {
+        assert!(find_references("&#123; #x abc#5 #12a a/b#8a C#").is_empty());
+        // The end of a longer path
+        assert!(find_references("https://example.com/owner/repo#3").is_empty());
+    }
+
+    #[test]
+    fn finds_issue_and_pull_request_links() {
+        let text = "https://github.com/owner/repo/issues/7 and www.github.com/a/b/pull/8/files, \
+                    github.com/c/d/issues/9#issuecomment-1";
+        assert_eq!(
+            find_references(text),
+            vec![other("owner/repo", 7), other("a/b", 8), other("c/d", 9)]
+        );
+        assert!(find_references(
+            "https://api.github.com/repos/o/r/issues/1 https://github.com/o/r/issues/new \
+             https://github.com/o/r/blob/main/README.md https://github.com/o/r/issues/2x"
+        )
+        .is_empty());
+    }
+
+    #[test]
+    fn reads_the_kind_from_the_words_before() {
+        assert_eq!(
+            kinds("Fixes #1, closes: #2, blocked by #3, depends on owner/repo#4"),
+            vec![(1, Closes), (2, Closes), (3, DependsOn), (4, DependsOn)]
+        );
+        assert_eq!(
+            kinds("Resolves https://github.com/o/r/issues/5"),
+            vec![(5, Closes)]
+        );
+        assert_eq!(kinds("Mentioned in #6"), vec![(6, Mentions)]);
+    }
+
+    #[test]
+    fn keywords_must_be_whole_words() {
+        assert_eq!(
+            kinds("prefixes #1 and unfixed #2"),
+            vec![(1, Mentions), (2, Mentions)]
+        );
+        assert_eq!(kinds("Prefix: fixes #3"), vec![(3, Closes)]);
+    }
+
+    #[test]
+    fn keeps_the_most_specific_kind() {
+        assert_eq!(kinds("See #1. Fixes #1"), vec![(1, Closes)]);
+        assert_eq!(kinds("Fixes #1, see #1"), vec![(1, Closes)]);
+    }
+
+    #[test]
+    fn looks_back_on_character_boundaries() {
+        let text = "€€€€€€ fixes #1";
+        assert_eq!(kind_before(text, text.find('#').unwrap()), Closes);
+        assert_eq!(kind_before("#1", 0), Mentions);
+    }
+}
@@ -1,4 +1,5 @@
 use super::github_client::get_client;
+use super::rate_limit::{with_retry, Resource};
 use serde::Serialize;
 use serde_json::json;
 use tauri::{command, AppHandle};
@@ -22,13 +23,15 @@ pub async fn fetch_repos() -> Result<Vec<RepoData>, String> {
     let octocrab = get_client()?;
 
     let mut all_repos = Vec::new();
-    let mut page = octocrab
-        .current()
-        .list_repos_for_authenticated_user()
-        .per_page(100)
-        .send()
-        .await
-        .map_err(|e| e.to_string())?;
+    let mut page = with_retry(Resource::Core, || async {
+        octocrab
+            .current()
+            .list_repos_for_authenticated_user()
+            .per_page(100)
+            .send()
+            .await
+    })
+    .await?;
 
     loop {
         let repos: Vec<RepoData> = page
@@ -57,10 +60,10 @@ pub async fn fetch_repos() -> Result<Vec<RepoData>, String> {
 
         all_repos.extend(repos);
 
-        match octocrab
-            .get_page::<octocrab::models::Repository>(&page.next)
-            .await
-            .map_err(|e| e.to_string())?
+        match with_retry(Resource::Core, || {
+            octocrab.get_page::<octocrab::models::Repository>(&page.next)
+        })
+        .await?
         {
             Some(next_page) => page = next_page,
             None => break,
@@ -0,0 +1,276 @@
+use super::interactions::fetch_single_issue;
+use super::issues::{IssueData, IssuesCache, RestIssue};
+use super::rate_limit::{with_rate_limit, with_retry, Resource};
+use super::references::{find_references, IssueRef};
+use crate::github::github_client::get_client;
+use serde::Serialize;
+use tauri::{command, AppHandle, State};
+
+/// One `- [ ]` or `- [x]` line of a markdown checklist.
+#[derive(Debug, Clone, Serialize)]
+pub struct TaskItem {
+    /// Position among the checklist items of the text, used to toggle it.
+    pub index: usize,
+    pub text: String,
+    pub checked: bool,
+    pub references: Vec<IssueRef>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct TaskList {
+    pub items: Vec<TaskItem>,
+    pub total: usize,
+    pub completed: usize,
+    /// Percentage of checked items, 0 when there are none.
+    pub completion: f64,
+}
+
+/// If `line` is a checklist item, returns the byte offset of the character inside the
+/// brackets, whether it's checked and the item's text.
+fn task_marker(line: &str) -> Option<(usize, bool, &str)> {
+    let content = line.trim_start();
+    let indent = line.len() - content.len();
+
+    let bullet = if content.starts_with(['-', '*', '+']) {
+        1
+    } else {
+        let digits = content.bytes().take_while(u8::is_ascii_digit).count();
+        if digits == 0 || !content[digits..].starts_with(['.', ')']) {
+            return None;
+        }
+        digits + 1
+    };
+
+    let rest = &content[bullet..];
+    let after_bullet = rest.trim_start_matches([' ', '\t']);
+    if after_bullet.len() == rest.len() {
+        return None;
+    }
+
+    let box_bytes = after_bullet.as_bytes();
+    if box_bytes.len() < 3 || box_bytes[0] != b'[' || box_bytes[2] != b']' {
+        return None;
+    }
+    let checked = match box_bytes[1] {
+        b' ' => false,
+        b'x' | b'X' => true,
+        _ => return None,
+    };
+    let text = &after_bullet[3..];
+    if !text.is_empty() && !text.starts_with([' ', '\t', '\r', '\n']) {
+        return None;
+    }
+
+    let offset = indent + bullet + (rest.len() - after_bullet.len()) + 1;
+    Some((offset, checked, text.trim()))
+}
+
+/// Walks the lines of `text` that are checklist items, skipping fenced code blocks.
+/// Yields each line's byte offset in `text` with its marker.
+fn task_lines(text: &str) -> impl Iterator<Item = (usize, (usize, bool, &str))> {
+    let mut fence: Option<&str> = None;
+    let mut offset = 0;
+
+    text.split_inclusive('\n').filter_map(move |line| {
+        let start = offset;
+        offset += line.len();
+
+        let trimmed = line.trim_start();
+        for marker in ["```", "~~~"] {
+            if trimmed.starts_with(marker) {
+                match fence {
+                    None => fence = Some(marker),
+                    Some(open) if open == marker => fence = None,
+                    Some(_) => {}
+                }
+                return None;
+            }
+        }
+        if fence.is_some() {
+            return None;
+        }
+
+        task_marker(line).map(|marker| (start, marker))
+    })
+}
+
+pub fn parse_tasks(text: Option<&str>) -> TaskList {
+    let items: Vec<TaskItem> = task_lines(text.unwrap_or_default())
+        .enumerate()
+        .map(|(index, (_, (_, checked, text)))| TaskItem {
+            index,
+            text: text.to_string(),
+            checked,
+            references: find_references(text),
+        })
+        .collect();
+
+    let total = items.len();
+    let completed = items.iter().filter(|item| item.checked).count();
+    TaskList {
+        items,
+        total,
+        completed,
+        completion: if total == 0 {
+            0.0
+        } else {
+            completed as f64 / total as f64 * 100.0
+        },
+    }
+}
+
+/// Checks or unchecks the `index`th checklist item, leaving the rest of the text as it was.
+fn set_task(text: &str, index: usize, checked: bool) -> Option<String> {
+    let (line_start, (offset, _, _)) = task_lines(text).nth(index)?;
+    let position = line_start + offset;
+
+    let mut updated = String::with_capacity(text.len());
+    updated.push_str(&text[..position]);
+    updated.push(if checked { 'x' } else { ' ' });
+    updated.push_str(&text[position + 1..]);
+    Some(updated)
+}
+
+/// Checks or unchecks an item of the issue's checklist by rewriting its body. The latest
+/// body is loaded first so edits made elsewhere aren't overwritten, and the item must
+/// still read `text`.
+#[command]
+pub async fn toggle_task_item(
+    app: AppHandle,
+    owner: String,
+    repo: String,
+    issue_number: i64,
+    index: usize,
+    text: String,
+    checked: bool,
+    cache: State<'_, IssuesCache>,
+) -> Result<IssueData, String> {
+    let octocrab = get_client()?;
+
+    let route = format!("/repos/{}/{}/issues/{}", owner, repo, issue_number);
+    let issue: RestIssue = with_retry(Resource::Core, || octocrab.get(&route, None::<&()>)).await?;
+    let body = issue.issue.body.unwrap_or_default();
+
+    let current = parse_tasks(Some(&body));
+    match current.items.get(index) {
+        Some(item) if item.text == text => {}
+        _ => {
+            return Err(String::from(
+                "The checklist was changed on GitHub, reload the issue and try again",
+            ))
+        }
+    }
+    let updated =
+        set_task(&body, index, checked).ok_or_else(|| format!("No checklist item {}", index))?;
+
+    with_rate_limit(Resource::Core, || async {
+        octocrab
+            .issues(&owner, &repo)
+            .update(issue_number as u64)
+            .body(&updated)
+            .send()
+            .await
+    })
+    .await?;
+
+    fetch_single_issue(app, owner, repo, issue_number, cache).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recognises_checklist_items() {
+        assert_eq!(
+            task_marker("- [ ] Write docs"),
+            Some((3, false, "Write docs"))
+        );
+        assert_eq!(task_marker("  * [x] Indented"), Some((5, true, "Indented")));
+        assert_eq!(task_marker("+ [X] Capital"), Some((3, true, "Capital")));
+        assert_eq!(
+            task_marker("12. [ ] Numbered"),
+            Some((5, false, "Numbered"))
+        );
+        assert_eq!(
+            task_marker("1) [x] Parenthesis"),
+            Some((4, true, "Parenthesis"))
+        );
+        assert_eq!(task_marker("- [ ]"), Some((3, false, "")));
+    }
+
+    #[test]
+    fn rejects_lines_that_only_look_like_items() {
+        assert_eq!(task_marker("-[ ] No space after the bullet"), None);
+        assert_eq!(task_marker("- [] Empty box"), None);
+        assert_eq!(task_marker("- [y] Unknown mark"), None);
+        assert_eq!(task_marker("- [ ]Text touching the box"), None);
+        assert_eq!(task_marker("[ ] No bullet"), None);
+        assert_eq!(task_marker("1.[ ] No space"), None);
+        assert_eq!(task_marker("- [é] Multibyte mark"), None);
+    }
+
+    #[test]
+    fn handles_crlf_line_endings() {
+        let body = "Plan\r\n- [ ] First\r\n- [x] Second\r\n";
+        let tasks = parse_tasks(Some(body));
+        let items: Vec<(&str, bool)> = tasks
+            .items
+            .iter()
+            .map(|item| (item.text.as_str(), item.checked))
+            .collect();
+        assert_eq!(items, vec![("First", false), ("Second", true)]);
+
+        assert_eq!(
+            set_task(body, 0, true).unwrap(),
+            "Plan\r\n- [x] First\r\n- [x] Second\r\n"
+        );
+        assert_eq!(
+            set_task(body, 1, false).unwrap(),
+            "Plan\r\n- [ ] First\r\n- [ ] Second\r\n"
+        );
+    }
+
+    #[test]
+    fn skips_items_in_fenced_code() {
+        let body =
+            "```md\n- [ ] Example\n~~~\n- [ ] Still code\n```\n- [ ] Real\n~~~\n- [x] Code\n~~~\n";
+        let tasks = parse_tasks(Some(body));
+        assert_eq!(tasks.total, 1);
+        assert_eq!(tasks.items[0].text, "Real");
+
+        let updated = set_task(body, 0, true).unwrap();
+        assert!(updated.contains("```\n- [x] Real\n"));
+        assert!(updated.contains("- [ ] Example\n"));
+        assert!(set_task(body, 1, true).is_none());
+    }
+
+    #[test]
+    fn counts_completion_and_references() {
+        let tasks = parse_tasks(Some(
+            "- [x] #1\n- [ ] owner/repo#2\n- [x] Done\n- [ ] Later",
+        ));
+        assert_eq!((tasks.total, tasks.completed), (4, 2));
+        assert_eq!(tasks.completion, 50.0);
+        assert_eq!(tasks.items[1].index, 1);
+        assert_eq!(
+            tasks.items[1].references,
+            vec![IssueRef {
+                repo: Some(String::from("owner/repo")),
+                number: 2,
+            }]
+        );
+
+        let empty = parse_tasks(None);
+        assert_eq!(empty.total, 0);
+        assert_eq!(empty.completion, 0.0);
+    }
+
+    #[test]
+    fn set_task_changes_only_the_mark() {
+        let body = "- [ ] One\n- [ ] Two\n";
+        assert_eq!(set_task(body, 1, true).unwrap(), "- [ ] One\n- [x] Two\n");
+        assert_eq!(set_task(body, 0, false).unwrap(), body);
+        assert!(set_task(body, 2, true).is_none());
+    }
+}
@@ -0,0 +1,603 @@
+use super::github_client::get_client;
+use super::issues::{IssueData, IssuesCache};
+use super::rate_limit::{with_rate_limit, with_retry, Resource};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tauri::{command, State};
+
+const TEMPLATE_DIR: &str = ".github/ISSUE_TEMPLATE";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct IssueTemplate {
+    pub file_name: String,
+    pub name: String,
+    pub about: Option<String>,
+    pub title: Option<String>,
+    pub labels: Vec<String>,
+    pub assignees: Vec<String>,
+    #[serde(flatten)]
+    pub body: TemplateBody,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum TemplateBody {
+    Markdown { body: String },
+    Form { fields: Vec<FormField> },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FormField {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub id: Option<String>,
+    #[serde(default)]
+    pub attributes: FormAttributes,
+    #[serde(default)]
+    pub validations: FormValidations,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FormAttributes {
+    pub label: Option<String>,
+    pub description: Option<String>,
+    pub placeholder: Option<String>,
+    pub value: Option<String>,
+    pub render: Option<String>,
+    #[serde(default)]
+    pub multiple: bool,
+    #[serde(default)]
+    pub options: Vec<FormOption>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FormValidations {
+    #[serde(default)]
+    pub required: bool,
+}
+
+/// Dropdowns list plain strings, checkboxes list objects with their own `required` flag.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum FormOption {
+    Plain(#[serde(deserialize_with = "scalar_to_string")] String),
+    Checkbox {
+        label: String,
+        #[serde(default)]
+        required: bool,
+    },
+}
+
+/// Dropdown options like `- 1.0` are parsed as numbers by YAML, but are labels to GitHub.
+fn scalar_to_string<'de, D>(deserializer: D) -> Result<String, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    match serde_yaml::Value::deserialize(deserializer)? {
+        serde_yaml::Value::String(value) => Ok(value),
+        serde_yaml::Value::Number(value) => Ok(value.to_string()),
+        serde_yaml::Value::Bool(value) => Ok(value.to_string()),
+        _ => Err(serde::de::Error::custom("expected a string option")),
+    }
+}
+
+impl FormOption {
+    fn label(&self) -> &str {
+        match self {
+            FormOption::Plain(label) => label,
+            FormOption::Checkbox { label, .. } => label,
+        }
+    }
+}
+
+/// Answers submitted for an issue form, keyed by field id (or label when a field has no id).
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum FormValue {
+    Text(String),
+    Selected(Vec<String>),
+}
+
+/// `labels` and `assignees` may be written as a list or a comma separated string.
+#[derive(Debug, Default, Deserialize)]
+#[serde(untagged)]
+enum StringOrList {
+    #[default]
+    Empty,
+    One(String),
+    Many(Vec<String>),
+}
+
+impl StringOrList {
+    fn into_vec(self) -> Vec<String> {
+        match self {
+            StringOrList::Empty => Vec::new(),
+            StringOrList::One(value) => value
+                .split(',')
+                .map(|item| item.trim().to_string())
+                .filter(|item| !item.is_empty())
+                .collect(),
+            StringOrList::Many(values) => values,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct MarkdownFrontMatter {
+    name: String,
+    about: Option<String>,
+    title: Option<String>,
+    #[serde(default)]
+    labels: StringOrList,
+    #[serde(default)]
+    assignees: StringOrList,
+}
+
+#[derive(Debug, Deserialize)]
+struct IssueForm {
+    name: String,
+    description: Option<String>,
+    title: Option<String>,
+    #[serde(default)]
+    labels: StringOrList,
+    #[serde(default)]
+    assignees: StringOrList,
+    body: Vec<FormField>,
+}
+
+fn parse_markdown_template(file_name: &str, content: &str) -> Result<IssueTemplate, String> {
+    let content = content.trim_start_matches('\u{feff}');
+    let rest = content
+        .strip_prefix("---")
+        .ok_or_else(|| format!("{} has no front matter", file_name))?;
+    let end = rest
+        .find("\n---")
+        .ok_or_else(|| format!("{} has unterminated front matter", file_name))?;
+
+    let front_matter: MarkdownFrontMatter = serde_yaml::from_str(&rest[..end])
+        .map_err(|e| format!("Failed to parse {}: {}", file_name, e))?;
+    let body = rest[end + 4..].trim_start_matches('-').trim().to_string();
+
+    Ok(IssueTemplate {
+        file_name: file_name.to_string(),
+        name: front_matter.name,
+        about: front_matter.about,
+        title: front_matter.title.filter(|title| !title.is_empty()),
+        labels: front_matter.labels.into_vec(),
+        assignees: front_matter.assignees.into_vec(),
+        body: TemplateBody::Markdown { body },
+    })
+}
+
+fn parse_form_template(file_name: &str, content: &str) -> Result<IssueTemplate, String> {
+    let form: IssueForm = serde_yaml::from_str(content)
+        .map_err(|e| format!("Failed to parse {}: {}", file_name, e))?;
+
+    Ok(IssueTemplate {
+        file_name: file_name.to_string(),
+        name: form.name,
+        about: form.description,
+        title: form.title.filter(|title| !title.is_empty()),
+        labels: form.labels.into_vec(),
+        assignees: form.assignees.into_vec(),
+        body: TemplateBody::Form { fields: form.body },
+    })
+}
+
+fn field_key(field: &FormField) -> Option<&str> {
+    field.id.as_deref().or(field.attributes.label.as_deref())
+}
+
+/// Checks required answers and dropdown choices, and renders the form the same way GitHub
+/// does on the web.
+fn render_form(
+    fields: &[FormField],
+    values: &HashMap<String, FormValue>,
+) -> Result<String, String> {
+    let mut missing = Vec::new();
+    let mut invalid = Vec::new();
+    let mut body = String::new();
+
+    for field in fields.iter().filter(|field| field.kind != "markdown") {
+        let label = field.attributes.label.clone().unwrap_or_default();
+        let value = field_key(field).and_then(|key| values.get(key));
+
+        let rendered = match field.kind.as_str() {
+            "checkboxes" => {
+                let selected: Vec<&str> = match value {
+                    Some(FormValue::Selected(selected)) => {
+                        selected.iter().map(String::as_str).collect()
+                    }
+                    Some(FormValue::Text(text)) => vec![text.as_str()],
+                    None => Vec::new(),
+                };
+
+                let mut lines = Vec::new();
+                for option in &field.attributes.options {
+                    let checked = selected.contains(&option.label());
+                    if let FormOption::Checkbox { required: true, .. } = option {
+                        if !checked {
+                            missing.push(format!("{}: {}", label, option.label()));
+                        }
+                    }
+                    lines.push(format!(
+                        "- [{}] {}",
+                        if checked { "X" } else { " " },
+                        option.label()
+                    ));
+                }
+                lines.join("\n")
+            }
+            "dropdown" => {
+                let selected: Vec<&str> = match value {
+                    Some(FormValue::Selected(selected)) => {
+                        selected.iter().map(String::as_str).collect()
+                    }
+                    Some(FormValue::Text(text)) if !text.trim().is_empty() => vec![text.trim()],
+                    _ => Vec::new(),
+                };
+
+                let options: Vec<&str> = field
+                    .attributes
+                    .options
+                    .iter()
+                    .map(FormOption::label)
+                    .collect();
+                for choice in selected.iter().filter(|choice| !options.contains(choice)) {
+                    invalid.push(format!("{}: {} is not an option", label, choice));
+                }
+                if selected.len() > 1 && !field.attributes.multiple {
+                    invalid.push(format!("{}: only one option can be selected", label));
+                }
+
+                if selected.is_empty() {
+                    if field.validations.required {
+                        missing.push(label.clone());
+                    }
+                    String::from("_No response_")
+                } else {
+                    selected.join(", ")
+                }
+            }
+            _ => {
+                let text = match value {
+                    Some(FormValue::Text(text)) => text.trim().to_string(),
+                    Some(FormValue::Selected(selected)) => selected.join(", "),
+                    None => String::new(),
+                };
+
+                if text.is_empty() {
+                    if field.validations.required {
+                        missing.push(label.clone());
+                    }
+                    String::from("_No response_")
+                } else {
+                    match &field.attributes.render {
+                        Some(language) => format!("```{}\n{}\n```", language, text),
+                        None => text,
+                    }
+                }
+            }
+        };
+
+        body.push_str(&format!("### {}\n\n{}\n\n", label, rendered));
+    }
+
+    if !missing.is_empty() {
+        return Err(format!("Missing required fields: {}", missing.join(", ")));
+    }
+    if !invalid.is_empty() {
+        return Err(format!("Invalid answers: {}", invalid.join(", ")));
+    }
+
+    Ok(body.trim_end().to_string())
+}
+
+async fn fetch_templates(owner: &str, repo: &str) -> Result<Vec<IssueTemplate>, String> {
+    let octocrab = get_client()?;
+
+    let listing = with_retry(Resource::Core, || async {
+        match octocrab
+            .repos(owner, repo)
+            .get_content()
+            .path(TEMPLATE_DIR)
+            .send()
+            .await
+        {
+            Ok(listing) => Ok(Some(listing)),
+            Err(octocrab::Error::GitHub { source, .. }) if source.status_code.as_u16() == 404 => {
+                Ok(None)
+            }
+            Err(e) => Err(e),
+        }
+    })
+    .await?;
+    let Some(listing) = listing else {
+        return Ok(Vec::new());
+    };
+
+    let mut templates = Vec::new();
+    for item in listing
+        .items
+        .into_iter()
+        .filter(|item| item.r#type == "file")
+    {
+        let is_markdown = item.name.ends_with(".md");
+        let is_form = item.name.ends_with(".yml") || item.name.ends_with(".yaml");
+        // config.yml configures the template chooser and isn't a template itself
+        if !(is_markdown || is_form) || item.name.starts_with("config.") {
+            continue;
+        }
+
+        let file = with_retry(Resource::Core, || async {
+            octocrab
+                .repos(owner, repo)
+                .get_content()
+                .path(&item.path)
+                .send()
+                .await
+        })
+        .await?;
+        let Some(content) = file.items.first().and_then(|file| file.decoded_content()) else {
+            continue;
+        };
+
+        let parsed = if is_markdown {
+            parse_markdown_template(&item.name, &content)
+        } else {
+            parse_form_template(&item.name, &content)
+        };
+
+        match parsed {
+            Ok(template) => templates.push(template),
+            Err(e) => println!("Skipping issue template: {}", e),
+        }
+    }
+
+    Ok(templates)
+}
+
+#[command]
+pub async fn list_issue_templates(
+    owner: String,
+    repo: String,
+) -> Result<Vec<IssueTemplate>, String> {
+    fetch_templates(&owner, &repo).await
+}
+
+#[command]
+pub async fn create_issue_from_template(
+    owner: String,
+    repo: String,
+    template: String,
+    title: String,
+    body: Option<String>,
+    fields: Option<HashMap<String, FormValue>>,
+    cache: State<'_, IssuesCache>,
+) -> Result<IssueData, String> {
+    let template = fetch_templates(&owner, &repo)
+        .await?
+        .into_iter()
+        .find(|t| t.file_name == template)
+        .ok_or_else(|| format!("Issue template {} not found", template))?;
+
+    let body = match &template.body {
+        TemplateBody::Markdown { body: default_body } => {
+            body.unwrap_or_else(|| default_body.clone())
+        }
+        TemplateBody::Form {
+            fields: form_fields,
+        } => render_form(form_fields, &fields.unwrap_or_default())?,
+    };
+
+    // The prefix alone doesn't make a title, check what the user wrote after it
+    let written = template
+        .title
+        .as_deref()
+        .and_then(|prefix| title.trim().strip_prefix(prefix.trim()))
+        .unwrap_or(&title);
+    if written.trim().is_empty() {
+        return Err("Title is required".to_string());
+    }
+    let title = match &template.title {
+        Some(prefix) if !title.starts_with(prefix.as_str()) => {
+            format!("{}{}", prefix, title)
+        }
+        _ => title,
+    };
+
+    let octocrab = get_client()?;
+    let issue = with_rate_limit(Resource::Core, || async {
+        octocrab
+            .issues(&owner, &repo)
+            .create(&title)
+            .body(&body)
+            .labels(template.labels.clone())
+            .assignees(template.assignees.clone())
+            .send()
+            .await
+    })
+    .await?;
+
+    let issue = IssueData::from(issue);
+    let cache_key = format!("{}/{}", owner, repo);
+    cache.upsert_issue(&cache_key, issue.clone())?;
+
+    Ok(issue)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BUG_FORM: &str = r#"
+name: Bug report
+description: Something isn't working
+title: "[Bug]: "
+labels: bug, triage
+body:
+  - type: markdown
+    attributes:
+      value: Thanks for taking the time!
+  - type: textarea
+    id: what-happened
+    attributes:
+      label: What happened?
+    validations:
+      required: true
+  - type: dropdown
+    id: version
+    attributes:
+      label: Version
+      options:
+        - 1.0
+        - 2.0
+    validations:
+      required: true
+  - type: textarea
+    id: logs
+    attributes:
+      label: Logs
+      render: shell
+  - type: checkboxes
+    id: terms
+    attributes:
+      label: Code of Conduct
+      options:
+        - label: I agree to follow the Code of Conduct
+          required: true
+        - label: I searched existing issues
+"#;
+
+    fn form_fields() -> Vec<FormField> {
+        match parse_form_template("bug.yml", BUG_FORM).unwrap().body {
+            TemplateBody::Form { fields } => fields,
+            TemplateBody::Markdown { .. } => panic!("expected a form"),
+        }
+    }
+
+    fn text(value: &str) -> FormValue {
+        FormValue::Text(value.to_string())
+    }
+
+    fn answers() -> HashMap<String, FormValue> {
+        HashMap::from([
+            (String::from("what-happened"), text("It crashed")),
+            (String::from("version"), text("2.0")),
+            (
+                String::from("terms"),
+                FormValue::Selected(vec![String::from("I agree to follow the Code of Conduct")]),
+            ),
+        ])
+    }
+
+    #[test]
+    fn parses_issue_forms() {
+        let template = parse_form_template("bug.yml", BUG_FORM).unwrap();
+        assert_eq!(template.name, "Bug report");
+        assert_eq!(template.about.as_deref(), Some("Something isn't working"));
+        assert_eq!(template.title.as_deref(), Some("[Bug]: "));
+        assert_eq!(template.labels, vec!["bug", "triage"]);
+
+        let fields = form_fields();
+        assert_eq!(fields.len(), 5);
+        assert!(fields[1].validations.required);
+        // Numeric dropdown options stay labels
+        let options: Vec<&str> = fields[2]
+            .attributes
+            .options
+            .iter()
+            .map(FormOption::label)
+            .collect();
+        assert_eq!(options, vec!["1.0", "2.0"]);
+        assert!(matches!(
+            fields[4].attributes.options[0],
+            FormOption::Checkbox { required: true, .. }
+        ));
+    }
+
+    #[test]
+    fn rejects_malformed_forms() {
+        let error = parse_form_template(
+            "broken.yml",
+            "name: Broken
+body: [unclosed",
+        )
+        .unwrap_err();
+        assert!(error.starts_with("Failed to parse broken.yml"));
+
+        // A form needs a body
+        assert!(parse_form_template("empty.yml", "name: Empty").is_err());
+    }
+
+    #[test]
+    fn parses_markdown_front_matter() {
+        let template = parse_markdown_template(
+            "feature.md",
+            "---\nname: Feature\nabout: Ideas\nlabels: [enhancement]\ntitle: ''\n---\n\nDescribe it\n",
+        )
+        .unwrap();
+        assert_eq!(template.name, "Feature");
+        assert_eq!(template.labels, vec!["enhancement"]);
+        assert_eq!(template.title, None);
+        assert!(
+            matches!(template.body, TemplateBody::Markdown { ref body } if body == "Describe it")
+        );
+
+        assert!(parse_markdown_template("plain.md", "Just text").is_err());
+    }
+
+    #[test]
+    fn renders_answers_like_github() {
+        let body = render_form(&form_fields(), &answers()).unwrap();
+        assert_eq!(
+            body,
+            "### What happened?\n\nIt crashed\n\n\
+             ### Version\n\n2.0\n\n\
+             ### Logs\n\n_No response_\n\n\
+             ### Code of Conduct\n\n\
+             - [X] I agree to follow the Code of Conduct\n\
+             - [ ] I searched existing issues"
+        );
+    }
+
+    #[test]
+    fn wraps_rendered_fields_in_a_code_block() {
+        let mut values = answers();
+        values.insert(String::from("logs"), text("panic at main.rs"));
+        let body = render_form(&form_fields(), &values).unwrap();
+        assert!(body.contains("### Logs\n\n```shell\npanic at main.rs\n```"));
+    }
+
+    #[test]
+    fn requires_required_fields_and_checkboxes() {
+        let mut values = answers();
+        values.remove("what-happened");
+        values.insert(String::from("terms"), FormValue::Selected(Vec::new()));
+
+        let error = render_form(&form_fields(), &values).unwrap_err();
+        assert_eq!(
+            error,
+            "Missing required fields: What happened?, \
+             Code of Conduct: I agree to follow the Code of Conduct"
+        );
+    }
+
+    #[test]
+    fn rejects_dropdown_choices_that_are_not_options() {
+        let mut values = answers();
+        values.insert(String::from("version"), text("3.0"));
+        let error = render_form(&form_fields(), &values).unwrap_err();
+        assert_eq!(error, "Invalid answers: Version: 3.0 is not an option");
+
+        values.insert(
+            String::from("version"),
+            FormValue::Selected(vec![String::from("1.0"), String::from("2.0")]),
+        );
+        let error = render_form(&form_fields(), &values).unwrap_err();
+        assert_eq!(
+            error,
+            "Invalid answers: Version: only one option can be selected"
+        );
+    }
+}
@@ -0,0 +1,303 @@
+use super::github_client::get_client;
+use super::graphql::normalize_timestamp;
+use super::issues::{CommentData, IssuesCache, ReactionSummary};
+use super::rate_limit::{all_pages, with_retry, Resource};
+use super::tasks::parse_tasks;
+use octocrab::Page;
+use serde::{Deserialize, Serialize};
+use tauri::{command, State};
+
+/// One entry in an issue's history. Comments are part of the stream so the view can render
+/// everything in order.
+#[derive(Debug, Clone, Serialize)]
+pub struct TimelineItem {
+    pub id: Option<i64>,
+    pub actor: Option<String>,
+    pub created_at: String,
+    #[serde(flatten)]
+    pub event: TimelineEvent,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum TimelineEvent {
+    Commented {
+        comment: CommentData,
+    },
+    Labeled {
+        label: String,
+        color: Option<String>,
+    },
+    Unlabeled {
+        label: String,
+        color: Option<String>,
+    },
+    Assigned {
+        assignee: String,
+    },
+    Unassigned {
+        assignee: String,
+    },
+    Closed {
+        state_reason: Option<String>,
+        // Set when a commit closed the issue
+        commit_id: Option<String>,
+    },
+    Reopened,
+    /// Mentioned from another issue or pull request, which is how a fixing PR shows up.
+    CrossReferenced {
+        source: ReferenceSource,
+    },
+    /// Mentioned from a commit message.
+    Referenced {
+        commit_id: Option<String>,
+    },
+    Renamed {
+        from: String,
+        to: String,
+    },
+    Milestoned {
+        milestone: String,
+    },
+    Demilestoned {
+        milestone: String,
+    },
+    /// Any event type git-pulse doesn't model yet, kept so the stream has no gaps.
+    Other {
+        event: String,
+    },
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ReferenceSource {
+    pub repo: Option<String>,
+    pub number: i64,
+    pub title: String,
+    pub state: String,
+    pub is_pull_request: bool,
+    pub url: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Login {
+    login: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawLabel {
+    name: String,
+    color: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawRename {
+    from: String,
+    to: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawMilestone {
+    title: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawSource {
+    issue: Option<RawSourceIssue>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawSourceIssue {
+    number: i64,
+    title: String,
+    state: String,
+    html_url: String,
+    pull_request: Option<serde_json::Value>,
+    repository: Option<RawRepository>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawRepository {
+    full_name: String,
+}
+
+/// The timeline endpoint returns a different shape per event, so every field is optional.
+#[derive(Debug, Deserialize)]
+struct RawEvent {
+    event: String,
+    id: Option<i64>,
+    actor: Option<Login>,
+    // Comments carry their author here instead of in `actor`
+    user: Option<Login>,
+    created_at: Option<String>,
+    updated_at: Option<String>,
+    body: Option<String>,
+    reactions: Option<ReactionSummary>,
+    label: Option<RawLabel>,
+    assignee: Option<Login>,
+    rename: Option<RawRename>,
+    milestone: Option<RawMilestone>,
+    commit_id: Option<String>,
+    state_reason: Option<String>,
+    source: Option<RawSource>,
+}
+
+impl RawEvent {
+    /// Returns `None` for events without a timestamp, such as commits on a pull request.
+    fn into_item(self) -> Option<TimelineItem> {
+        let created_at = normalize_timestamp(self.created_at.as_deref()?);
+        let actor = self
+            .actor
+            .or(self.user)
+            .map(|user| user.login)
+            .filter(|login| !login.is_empty());
+
+        let event = match self.event.as_str() {
+            "commented" => TimelineEvent::Commented {
+                comment: CommentData {
+                    id: self.id.unwrap_or_default(),
+                    tasks: parse_tasks(self.body.as_deref()),
+                    body: self.body.unwrap_or_default(),
+                    created_at: created_at.clone(),
+                    updated_at: self.updated_at.as_deref().map(normalize_timestamp),
+                    author: actor.clone().unwrap_or_default(),
+                    reactions: self.reactions,
+                    pending: false,
+                },
+            },
+            "labeled" | "unlabeled" => {
+                let label = self.label?;
+                if self.event == "labeled" {
+                    TimelineEvent::Labeled {
+                        label: label.name,
+                        color: label.color,
+                    }
+                } else {
+                    TimelineEvent::Unlabeled {
+                        label: label.name,
+                        color: label.color,
+                    }
+                }
+            }
+            "assigned" => TimelineEvent::Assigned {
+                assignee: self.assignee?.login,
+            },
+            "unassigned" => TimelineEvent::Unassigned {
+                assignee: self.assignee?.login,
+            },
+            "closed" => TimelineEvent::Closed {
+                state_reason: self.state_reason,
+                commit_id: self.commit_id,
+            },
+            "reopened" => TimelineEvent::Reopened,
+            "cross-referenced" => {
+                let issue = self.source?.issue?;
+                TimelineEvent::CrossReferenced {
+                    source: ReferenceSource {
+                        repo: issue.repository.map(|repository| repository.full_name),
+                        number: issue.number,
+                        title: issue.title,
+                        state: issue.state,
+                        is_pull_request: issue.pull_request.is_some(),
+                        url: issue.html_url,
+                    },
+                }
+            }
+            "referenced" => TimelineEvent::Referenced {
+                commit_id: self.commit_id,
+            },
+            "renamed" => {
+                let rename = self.rename?;
+                TimelineEvent::Renamed {
+                    from: rename.from,
+                    to: rename.to,
+                }
+            }
+            "milestoned" => TimelineEvent::Milestoned {
+                milestone: self.milestone?.title,
+            },
+            "demilestoned" => TimelineEvent::Demilestoned {
+                milestone: self.milestone?.title,
+            },
+            _ => TimelineEvent::Other { event: self.event },
+        };
+
+        Some(TimelineItem {
+            id: self.id,
+            actor,
+            created_at,
+            event,
+        })
+    }
+}
+
+fn comment_items(comments: &[CommentData]) -> Vec<TimelineItem> {
+    comments
+        .iter()
+        .map(|comment| TimelineItem {
+            id: Some(comment.id),
+            actor: Some(comment.author.clone()),
+            created_at: comment.created_at.clone(),
+            event: TimelineEvent::Commented {
+                comment: comment.clone(),
+            },
+        })
+        .collect()
+}
+
+/// Returns the issue's comments and events as one stream, oldest first. Comments loaded along
+/// the way are stored in the cache. Offline, the cached comments are returned on their own.
+#[command]
+pub async fn fetch_issue_timeline(
+    owner: String,
+    repo: String,
+    issue_number: i64,
+    cache: State<'_, IssuesCache>,
+) -> Result<Vec<TimelineItem>, String> {
+    let cache_key = format!("{}/{}", owner, repo);
+    let cached_issue = {
+        let cache_guard = cache.get_cache().lock().map_err(|e| e.to_string())?;
+        cache_guard
+            .get(&cache_key)
+            .and_then(|cached| cached.issues.iter().find(|i| i.number == issue_number))
+            .cloned()
+    };
+
+    let octocrab = get_client()?;
+    let route = format!("/repos/{}/{}/issues/{}/timeline", owner, repo, issue_number);
+    let events = match with_retry(Resource::Core, || {
+        octocrab.get::<Page<RawEvent>, _, _>(&route, Some(&[("per_page", "100")]))
+    })
+    .await
+    {
+        Ok(page) => all_pages(&octocrab, page).await,
+        Err(e) => Err(e),
+    };
+
+    let events = match events {
+        Ok(events) => events,
+        Err(e) => {
+            println!("Error fetching timeline for #{}: {}", issue_number, e);
+            return cached_issue
+                .map(|issue| comment_items(&issue.comments))
+                .ok_or(e);
+        }
+    };
+
+    let mut items: Vec<TimelineItem> = events.into_iter().filter_map(RawEvent::into_item).collect();
+    // Timestamps are normalised to UTC, so they sort as strings
+    items.sort_by(|a, b| a.created_at.cmp(&b.created_at));
+
+    // The timeline holds every comment, so it doubles as a full comment load
+    if let Some(issue) = cached_issue {
+        let comments = items
+            .iter()
+            .filter_map(|item| match &item.event {
+                TimelineEvent::Commented { comment } => Some(comment.clone()),
+                _ => None,
+            })
+            .collect();
+        cache.set_issue_comments(&cache_key, issue_number, &issue.updated_at, Ok(comments))?;
+    }
+
+    Ok(items)
+}
@@ -1,20 +1,31 @@
 pub mod ais;
 mod check_auth;
+mod drafts;
 mod github;
 mod obsidian;
+mod outbox;
 mod recents;
+mod search;
 mod settings;
+mod storage;
+mod sync;
 mod window_manager;
 
 use dotenvy::dotenv;
 use github::github_client::init_github_client;
+use github::inbox::fetch_inbox;
+use github::inbox::InboxCache;
 use github::issues::check_cache_status;
 use github::issues::create_new_issue;
 use github::issues::fetch_issues;
 use github::issues::get_cached_issue;
 use github::issues::get_pinned_repos;
+use github::issues::get_repo_sync_status;
 use github::issues::save_pinned_repos;
 use github::issues::IssuesCache;
+use github::pulls::fetch_pull_request;
+use github::pulls::fetch_pull_requests;
+use github::pulls::PullRequestsCache;
 use tauri::{AppHandle, Manager};
 
 use std::env;
@@ -30,23 +41,96 @@ use recents::recents::clear_recents;
 use recents::recents::load_recents;
 use recents::recents::save_recents;
 
+use github::comments::fetch_issue_comments;
+use github::comments::prefetch_issue_comments;
+
+use github::interactions::add_issue_assignees;
 use github::interactions::add_issue_comment;
+use github::interactions::add_issue_labels;
+use github::interactions::close_issue;
+use github::interactions::delete_issue;
 use github::interactions::delete_issue_comment;
 use github::interactions::edit_issue_comment;
+use github::interactions::fetch_single_issue;
+use github::interactions::lock_issue;
+use github::interactions::pin_issue;
+use github::interactions::remove_issue_assignees;
+use github::interactions::remove_issue_label;
+use github::interactions::reopen_issue;
+use github::interactions::set_issue_milestone;
+use github::interactions::transfer_issue;
+use github::interactions::unlock_issue;
+use github::interactions::unpin_issue;
+use github::interactions::update_issue;
+
+use github::milestones::close_milestone;
+use github::milestones::create_milestone;
+use github::milestones::list_milestones;
+use github::milestones::update_milestone;
+
+use github::notifications::fetch_notifications;
+use github::notifications::mark_notification_read;
+use github::notifications::mark_repo_notifications_read;
+use github::notifications::mute_notification_thread;
+use github::notifications::unsubscribe_notification_thread;
+use github::notifications::NotificationsCache;
 
 use github::oauth::get_username;
 use github::oauth::initiate_device_login;
 use github::oauth::poll_for_token;
 
+use github::rate_limit::get_rate_limit_status;
+use github::reactions::add_comment_reaction;
+use github::reactions::add_issue_reaction;
+use github::reactions::remove_comment_reaction;
+use github::reactions::remove_issue_reaction;
+
 use github::repos::add_repos_to_store;
 use github::repos::fetch_repos;
 use github::repos::get_repos_from_store;
 
+use github::tasks::toggle_task_item;
+
+use github::crossref::get_issue_references;
+use github::crossref::get_reference_graph;
+
+use github::templates::create_issue_from_template;
+use github::templates::list_issue_templates;
+
+use github::timeline::fetch_issue_timeline;
+
+use search::duplicates::find_duplicate_issues;
+use search::index::search_issues;
+use search::query::query_issues;
+
+use drafts::drafts::discard_draft;
+use drafts::drafts::get_draft;
+use drafts::drafts::list_drafts;
+use drafts::drafts::save_draft;
+use drafts::drafts::DraftStore;
+use outbox::queue::get_outbox;
+use outbox::queue::Outbox;
+use outbox::replay::discard_outbox_entry;
+use outbox::replay::flush_outbox;
+use outbox::replay::retry_outbox_entry;
+use sync::notify::get_notification_prefs;
+use sync::notify::set_issue_watched;
+use sync::notify::set_quiet_hours;
+use sync::notify::set_repo_muted;
+use sync::notify::set_repo_watched;
+use sync::notify::DesktopNotifier;
+use sync::scheduler::get_sync_settings;
+use sync::scheduler::get_sync_status;
+use sync::scheduler::save_sync_settings;
+use sync::scheduler::trigger_sync;
+use sync::scheduler::SyncScheduler;
+
 use ais::changelog::generate_and_save_changelog;
 use ais::file_suggestions::check_file_recommendations_cache;
 use ais::file_suggestions::get_relevant_files;
 use ais::file_suggestions::RecommendationsCache;
 
+use storage::store::IssueStore;
 use tauri_plugin_store::StoreExt;
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
@@ -66,9 +150,17 @@ pub fn run() {
     tauri::Builder::default()
         .plugin(tauri_plugin_store::Builder::new().build())
         .manage(IssuesCache::default())
+        .manage(PullRequestsCache::default())
+        .manage(InboxCache::default())
+        .manage(NotificationsCache::default())
+        .manage(SyncScheduler::default())
+        .manage(DesktopNotifier::default())
+        .manage(Outbox::default())
+        .manage(DraftStore::default())
         .manage(RecommendationsCache::default())
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_shell::init())
+        .plugin(tauri_plugin_notification::init())
         .invoke_handler(tauri::generate_handler![
             save_to_obsidian,
             check_cache_status,
@@ -95,7 +187,68 @@ pub fn run() {
             get_relevant_files,
             delete_issue_comment,
             check_file_recommendations_cache,
-            edit_issue_comment
+            edit_issue_comment,
+            fetch_single_issue,
+            get_repo_sync_status,
+            fetch_pull_requests,
+            fetch_pull_request,
+            update_issue,
+            close_issue,
+            reopen_issue,
+            add_issue_labels,
+            remove_issue_label,
+            add_issue_assignees,
+            remove_issue_assignees,
+            set_issue_milestone,
+            lock_issue,
+            unlock_issue,
+            pin_issue,
+            unpin_issue,
+            transfer_issue,
+            delete_issue,
+            toggle_task_item,
+            get_issue_references,
+            get_reference_graph,
+            list_issue_templates,
+            create_issue_from_template,
+            list_milestones,
+            create_milestone,
+            update_milestone,
+            close_milestone,
+            search_issues,
+            find_duplicate_issues,
+            query_issues,
+            fetch_issue_comments,
+            prefetch_issue_comments,
+            get_rate_limit_status,
+            add_issue_reaction,
+            remove_issue_reaction,
+            add_comment_reaction,
+            remove_comment_reaction,
+            fetch_issue_timeline,
+            fetch_inbox,
+            fetch_notifications,
+            mark_notification_read,
+            mark_repo_notifications_read,
+            mute_notification_thread,
+            unsubscribe_notification_thread,
+            get_sync_settings,
+            save_sync_settings,
+            get_sync_status,
+            trigger_sync,
+            get_notification_prefs,
+            set_issue_watched,
+            set_repo_watched,
+            set_repo_muted,
+            set_quiet_hours,
+            get_outbox,
+            flush_outbox,
+            retry_outbox_entry,
+            discard_outbox_entry,
+            save_draft,
+            get_draft,
+            list_drafts,
+            discard_draft
         ])
         .setup(move |app| {
             load_env(&app.handle())?;
@@ -103,10 +256,20 @@ pub fn run() {
             let _auth_store = app.store("auth.json")?;
             let _repo_store = app.store("repos.json")?;
 
+            // Open the on-disk issue store and load it into the cache
+            let db_path = app.path().app_data_dir()?.join("issues.db");
+            let issue_store = IssueStore::open(&db_path)?;
+            app.state::<IssuesCache>().attach_store(issue_store)?;
+
+            // Writes made offline in an earlier session are still waiting to be sent
+            let outbox_path = app.path().app_data_dir()?.join("outbox.json");
+            app.state::<Outbox>().attach(outbox_path)?;
+
             let app_handle = app.handle();
 
             // Window management
             window_manager::setup_window_management(app)?;
+            sync::notify::setup_focus_prompt(app);
 
             tauri::async_runtime::block_on(async move {
                 match get_stored_auth(&app_handle) {
@@ -124,6 +287,11 @@ pub fn run() {
             })
             .expect("Runtime error during setup");
 
+            // Keep pinned and stored repos fresh in the background
+            sync::scheduler::start(app.handle());
+            // Send writes queued while offline as soon as GitHub is reachable
+            outbox::replay::start_watcher(app.handle());
+
             Ok(())
         })
         .run(tauri::generate_context!())
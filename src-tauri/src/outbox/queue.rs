@@ -0,0 +1,348 @@
+use crate::github::issues::{CommentData, IssueData, IssuesCache};
+use crate::github::tasks::parse_tasks;
+use crate::sync::scheduler::is_online;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::future::Future;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, OnceLock};
+use tauri::{command, State};
+
+/// A write made while GitHub couldn't be reached.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Mutation {
+    AddComment {
+        owner: String,
+        repo: String,
+        issue_number: i64,
+        body: String,
+        /// The issue's state when the comment was written.
+        issue_state: Option<String>,
+    },
+    EditComment {
+        owner: String,
+        repo: String,
+        issue_number: i64,
+        comment_id: i64,
+        body: String,
+        /// The comment's text before the edit, to spot edits made elsewhere.
+        base_body: Option<String>,
+    },
+    CreateIssue {
+        owner: String,
+        repo: String,
+        title: String,
+        body: String,
+    },
+}
+
+impl Mutation {
+    pub fn repo_key(&self) -> String {
+        let (owner, repo) = match self {
+            Mutation::AddComment { owner, repo, .. }
+            | Mutation::EditComment { owner, repo, .. }
+            | Mutation::CreateIssue { owner, repo, .. } => (owner, repo),
+        };
+        format!("{}/{}", owner, repo)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutboxEntry {
+    pub id: i64,
+    pub mutation: Mutation,
+    pub queued_at: String,
+    #[serde(default)]
+    pub attempts: u32,
+    #[serde(default)]
+    pub last_error: Option<String>,
+    /// Why the write wasn't replayed. The entry stays put until it's retried or discarded.
+    #[serde(default)]
+    pub conflict: Option<String>,
+    /// Set when the user chose to send the write despite a conflict.
+    #[serde(default)]
+    pub force: bool,
+}
+
+impl OutboxEntry {
+    /// The id of the local stand-in for a new comment, or the number of a new issue.
+    pub fn placeholder_id(&self) -> i64 {
+        -self.id
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct OutboxFile {
+    next_id: i64,
+    entries: Vec<OutboxEntry>,
+}
+
+/// Writes waiting to be sent, in the order they were made. Kept in a JSON file so they
+/// survive restarts.
+#[derive(Debug, Default, Clone)]
+pub struct Outbox {
+    path: Arc<OnceLock<PathBuf>>,
+    file: Arc<Mutex<OutboxFile>>,
+    pub(crate) flushing: Arc<tokio::sync::Mutex<()>>,
+}
+
+impl Outbox {
+    /// Loads the queued writes from `path` and saves to it from now on.
+    pub fn attach(&self, path: PathBuf) -> Result<(), String> {
+        if let Ok(contents) = fs::read_to_string(&path) {
+            let stored: OutboxFile = serde_json::from_str(&contents)
+                .map_err(|e| format!("Failed to parse outbox: {}", e))?;
+            println!("Loaded {} queued writes", stored.entries.len());
+            *self.file.lock().map_err(|e| e.to_string())? = stored;
+        }
+
+        self.path
+            .set(path)
+            .map_err(|_| "Outbox already attached".to_string())
+    }
+
+    fn save(&self, file: &OutboxFile) -> Result<(), String> {
+        let Some(path) = self.path.get() else {
+            return Ok(());
+        };
+        let json = serde_json::to_string_pretty(file)
+            .map_err(|e| format!("Failed to serialize outbox: {}", e))?;
+
+        // Write then rename, so a crash mid-write can't lose the queue
+        let temp = path.with_extension("json.tmp");
+        fs::write(&temp, json).map_err(|e| e.to_string())?;
+        fs::rename(&temp, path).map_err(|e| e.to_string())
+    }
+
+    pub fn entries(&self) -> Result<Vec<OutboxEntry>, String> {
+        Ok(self.file.lock().map_err(|e| e.to_string())?.entries.clone())
+    }
+
+    /// Whether writes are waiting to be replayed. Conflicts don't hold up new writes.
+    pub fn has_pending(&self) -> Result<bool, String> {
+        let file = self.file.lock().map_err(|e| e.to_string())?;
+        Ok(file.entries.iter().any(|entry| entry.conflict.is_none()))
+    }
+
+    pub fn get(&self, id: i64) -> Result<Option<OutboxEntry>, String> {
+        let file = self.file.lock().map_err(|e| e.to_string())?;
+        Ok(file.entries.iter().find(|entry| entry.id == id).cloned())
+    }
+
+    pub fn push(&self, mutation: Mutation) -> Result<OutboxEntry, String> {
+        let mut file = self.file.lock().map_err(|e| e.to_string())?;
+        file.next_id += 1;
+        let entry = OutboxEntry {
+            id: file.next_id,
+            mutation,
+            queued_at: Utc::now().to_rfc3339(),
+            attempts: 0,
+            last_error: None,
+            conflict: None,
+            force: false,
+        };
+        file.entries.push(entry.clone());
+        self.save(&file)?;
+
+        Ok(entry)
+    }
+
+    pub fn update(&self, entry: &OutboxEntry) -> Result<(), String> {
+        let mut file = self.file.lock().map_err(|e| e.to_string())?;
+        if let Some(existing) = file.entries.iter_mut().find(|e| e.id == entry.id) {
+            *existing = entry.clone();
+        }
+        self.save(&file)
+    }
+
+    pub fn remove(&self, id: i64) -> Result<Option<OutboxEntry>, String> {
+        let mut file = self.file.lock().map_err(|e| e.to_string())?;
+        let Some(position) = file.entries.iter().position(|entry| entry.id == id) else {
+            return Ok(None);
+        };
+        let entry = file.entries.remove(position);
+        self.save(&file)?;
+
+        Ok(Some(entry))
+    }
+
+    /// Changes the text of a comment that hasn't been sent yet, found by its placeholder id.
+    pub fn rewrite_comment(
+        &self,
+        placeholder: i64,
+        text: String,
+    ) -> Result<Option<OutboxEntry>, String> {
+        let mut file = self.file.lock().map_err(|e| e.to_string())?;
+        let Some(entry) = file
+            .entries
+            .iter_mut()
+            .find(|entry| entry.placeholder_id() == placeholder)
+        else {
+            return Ok(None);
+        };
+        let Mutation::AddComment { body, .. } = &mut entry.mutation else {
+            return Ok(None);
+        };
+        *body = text;
+        let entry = entry.clone();
+        self.save(&file)?;
+
+        Ok(Some(entry))
+    }
+
+    /// Points queued comments on an issue created offline at the number GitHub gave it.
+    pub fn renumber(&self, key: &str, placeholder: i64, number: i64) -> Result<(), String> {
+        let mut file = self.file.lock().map_err(|e| e.to_string())?;
+        for entry in file.entries.iter_mut() {
+            let same_repo = entry.mutation.repo_key() == key;
+            if let Mutation::AddComment { issue_number, .. } = &mut entry.mutation {
+                if same_repo && *issue_number == placeholder {
+                    *issue_number = number;
+                }
+            }
+        }
+        self.save(&file)
+    }
+}
+
+pub enum Written<T> {
+    Sent(T),
+    Queued(OutboxEntry),
+}
+
+/// Runs `write`, or queues `mutation` instead when earlier writes are still waiting or the
+/// write failed because the machine is offline. Other failures are returned as usual.
+pub async fn write_or_queue<T, F, Fut>(
+    outbox: &Outbox,
+    mutation: Mutation,
+    write: F,
+) -> Result<Written<T>, String>
+where
+    F: FnOnce() -> Fut,
+    Fut: Future<Output = Result<T, String>>,
+{
+    // Sending now would overtake the queue
+    if outbox.has_pending()? {
+        return Ok(Written::Queued(outbox.push(mutation)?));
+    }
+
+    match write().await {
+        Ok(value) => Ok(Written::Sent(value)),
+        Err(e) if !is_online().await => {
+            println!("Offline, queueing write: {}", e);
+            Ok(Written::Queued(outbox.push(mutation)?))
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Puts a queued write into the cache, marked pending, and returns the issue it affects.
+pub fn show_pending(
+    cache: &IssuesCache,
+    entry: &OutboxEntry,
+    login: &str,
+) -> Result<IssueData, String> {
+    let key = entry.mutation.repo_key();
+    let not_cached =
+        || String::from("Saved to the outbox, it will be sent when GitHub can be reached again");
+
+    match &entry.mutation {
+        Mutation::AddComment {
+            issue_number, body, ..
+        } => {
+            let comment = CommentData {
+                id: entry.placeholder_id(),
+                body: body.clone(),
+                tasks: parse_tasks(Some(body)),
+                created_at: entry.queued_at.clone(),
+                updated_at: None,
+                author: login.to_string(),
+                reactions: None,
+                pending: true,
+            };
+            cache
+                .apply_pending_comment(&key, *issue_number, comment)?
+                .ok_or_else(not_cached)
+        }
+        Mutation::EditComment {
+            issue_number,
+            comment_id,
+            body,
+            ..
+        } => {
+            let existing = {
+                let cache_guard = cache.get_cache().lock().map_err(|e| e.to_string())?;
+                cache_guard
+                    .get(&key)
+                    .and_then(|cached| cached.issues.iter().find(|i| i.number == *issue_number))
+                    .and_then(|issue| issue.comments.iter().find(|c| c.id == *comment_id))
+                    .cloned()
+            };
+            let Some(existing) = existing else {
+                return Err(not_cached());
+            };
+
+            let comment = CommentData {
+                body: body.clone(),
+                tasks: parse_tasks(Some(body)),
+                updated_at: Some(entry.queued_at.clone()),
+                pending: true,
+                ..existing
+            };
+            cache
+                .apply_pending_comment(&key, *issue_number, comment)?
+                .ok_or_else(not_cached)
+        }
+        Mutation::CreateIssue { title, body, .. } => {
+            let issue = IssueData {
+                number: entry.placeholder_id(),
+                title: title.clone(),
+                state: String::from("open"),
+                state_reason: None,
+                created_at: entry.queued_at.clone(),
+                updated_at: entry.queued_at.clone(),
+                body: Some(body.clone()),
+                tasks: parse_tasks(Some(body)),
+                labels: Vec::new(),
+                assignees: Vec::new(),
+                milestone: None,
+                comments: Vec::new(),
+                comments_count: 0,
+                comments_loaded: true,
+                comments_error: None,
+                creator: login.to_string(),
+                is_pull_request: false,
+                reactions: None,
+                pending: true,
+                locked: false,
+                lock_reason: None,
+                pinned: None,
+            };
+            cache.upsert_issue(&key, issue.clone())?;
+            Ok(issue)
+        }
+    }
+}
+
+/// Takes a queued write back out of the cache.
+pub fn hide_pending(cache: &IssuesCache, entry: &OutboxEntry) -> Result<(), String> {
+    let key = entry.mutation.repo_key();
+    match &entry.mutation {
+        Mutation::AddComment { issue_number, .. } => {
+            cache.resolve_pending_comment(&key, *issue_number, entry.placeholder_id())
+        }
+        Mutation::EditComment {
+            issue_number,
+            comment_id,
+            ..
+        } => cache.resolve_pending_comment(&key, *issue_number, *comment_id),
+        Mutation::CreateIssue { .. } => cache.remove_issue(&key, entry.placeholder_id()),
+    }
+}
+
+#[command]
+pub async fn get_outbox(outbox: State<'_, Outbox>) -> Result<Vec<OutboxEntry>, String> {
+    outbox.entries()
+}
@@ -0,0 +1,410 @@
+use super::queue::{hide_pending, Mutation, Outbox, OutboxEntry};
+use crate::github::get_username;
+use crate::github::github_client::get_client;
+use crate::github::interactions::fetch_single_issue;
+use crate::github::issues::{IssueData, IssuesCache, RestIssue};
+use crate::github::rate_limit::{with_rate_limit, with_retry, Classify, Failure, Resource};
+use crate::sync::scheduler::is_online;
+use chrono::{DateTime, Duration, SecondsFormat, Utc};
+use octocrab::models::issues::Comment;
+use octocrab::models::{CommentId, IssueState};
+use octocrab::Octocrab;
+use std::future::Future;
+use tauri::{command, AppHandle, Emitter, Manager, State};
+
+/// Writes that keep failing while online are set aside after this many tries, so they
+/// don't hold up the rest of the queue.
+const MAX_ATTEMPTS: u32 = 5;
+/// A request that timed out may still have created its issue. The search for it starts
+/// this long before the write was queued, to allow for the request itself and clock skew.
+const CREATED_LOOKBACK_MINS: i64 = 10;
+/// How often queued writes are tried again while GitHub can't be reached.
+const WATCH_INTERVAL_SECS: u64 = 30;
+
+enum Replayed {
+    /// Sent. Carries the issue when the write created one.
+    Sent(Option<IssueData>),
+    Conflict(String),
+}
+
+/// Turns GitHub refusing a request outright (gone, archived, locked) into a value, so it
+/// isn't retried like a network failure.
+async fn accepted<T>(
+    call: impl Future<Output = octocrab::Result<T>>,
+) -> octocrab::Result<Result<T, String>> {
+    match call.await {
+        Ok(value) => Ok(Ok(value)),
+        Err(e) => match &e {
+            octocrab::Error::GitHub { source, .. } if e.classify() == Failure::Fatal => {
+                Ok(Err(source.message.clone()))
+            }
+            _ => Err(e),
+        },
+    }
+}
+
+/// Finds an issue that an earlier attempt at a queued `CreateIssue` already created: one
+/// by the same author with the same title, created since the write was queued.
+async fn find_created_issue(
+    octocrab: &Octocrab,
+    entry: &OutboxEntry,
+    login: Option<&str>,
+) -> Result<Option<IssueData>, String> {
+    let Mutation::CreateIssue {
+        owner, repo, title, ..
+    } = &entry.mutation
+    else {
+        return Ok(None);
+    };
+    let since = DateTime::parse_from_rfc3339(&entry.queued_at)
+        .map(|queued_at| queued_at.with_timezone(&Utc))
+        .unwrap_or_else(|_| Utc::now())
+        - Duration::minutes(CREATED_LOOKBACK_MINS);
+
+    // `since` filters on the last update, which is never before the issue was created
+    let mut route = format!(
+        "/repos/{}/{}/issues?state=all&sort=created&direction=desc&per_page=100&since={}",
+        owner,
+        repo,
+        since.to_rfc3339_opts(SecondsFormat::Secs, true)
+    );
+    if let Some(login) = login {
+        route.push_str(&format!("&creator={}", login));
+    }
+    let issues: Vec<RestIssue> =
+        with_retry(Resource::Core, || octocrab.get(&route, None::<&()>)).await?;
+
+    Ok(issues
+        .into_iter()
+        .find(|rest| {
+            rest.issue.pull_request.is_none()
+                && rest.issue.created_at >= since
+                && rest.issue.title == *title
+        })
+        .map(IssueData::from))
+}
+
+/// Sends one queued write, checking first that it still makes sense. `Err` means it should
+/// be tried again later.
+async fn replay(
+    octocrab: &Octocrab,
+    entry: &OutboxEntry,
+    login: Option<&str>,
+) -> Result<Replayed, String> {
+    match &entry.mutation {
+        Mutation::AddComment {
+            owner,
+            repo,
+            issue_number,
+            body,
+            issue_state,
+        } => {
+            if *issue_number < 0 {
+                return Ok(Replayed::Conflict(String::from(
+                    "The issue this comment belongs to hasn't been created yet",
+                )));
+            }
+
+            if !entry.force {
+                let route = format!("/repos/{}/{}/issues/{}", owner, repo, issue_number);
+                let issue = match with_retry(Resource::Core, || {
+                    accepted(octocrab.get::<RestIssue, _, _>(&route, None::<&()>))
+                })
+                .await?
+                {
+                    Ok(issue) => issue.issue,
+                    Err(reason) => {
+                        return Ok(Replayed::Conflict(format!(
+                            "Issue #{} can't be reached: {}",
+                            issue_number, reason
+                        )))
+                    }
+                };
+
+                if issue.locked {
+                    return Ok(Replayed::Conflict(format!(
+                        "Issue #{} was locked",
+                        issue_number
+                    )));
+                }
+                if issue_state.as_deref() == Some("open")
+                    && matches!(issue.state, IssueState::Closed)
+                {
+                    return Ok(Replayed::Conflict(format!(
+                        "Issue #{} was closed while you were offline",
+                        issue_number
+                    )));
+                }
+            }
+
+            let sent = with_rate_limit(Resource::Core, || {
+                accepted(async {
+                    octocrab
+                        .issues(owner, repo)
+                        .create_comment(*issue_number as u64, body)
+                        .await
+                })
+            })
+            .await?;
+
+            Ok(match sent {
+                Ok(_) => Replayed::Sent(None),
+                Err(reason) => {
+                    Replayed::Conflict(format!("GitHub rejected the comment: {}", reason))
+                }
+            })
+        }
+        Mutation::EditComment {
+            owner,
+            repo,
+            comment_id,
+            body,
+            base_body,
+            ..
+        } => {
+            if !entry.force {
+                let route = format!("/repos/{}/{}/issues/comments/{}", owner, repo, comment_id);
+                let current = match with_retry(Resource::Core, || {
+                    accepted(octocrab.get::<Comment, _, _>(&route, None::<&()>))
+                })
+                .await?
+                {
+                    Ok(comment) => comment,
+                    Err(reason) => {
+                        return Ok(Replayed::Conflict(format!(
+                            "The comment can't be reached: {}",
+                            reason
+                        )))
+                    }
+                };
+
+                if base_body.is_some() && current.body != *base_body {
+                    return Ok(Replayed::Conflict(String::from(
+                        "The comment was edited elsewhere while you were offline",
+                    )));
+                }
+            }
+
+            let sent = with_rate_limit(Resource::Core, || {
+                accepted(async {
+                    octocrab
+                        .issues(owner, repo)
+                        .update_comment(CommentId(*comment_id as u64), body)
+                        .await
+                })
+            })
+            .await?;
+
+            Ok(match sent {
+                Ok(_) => Replayed::Sent(None),
+                Err(reason) => Replayed::Conflict(format!("GitHub rejected the edit: {}", reason)),
+            })
+        }
+        Mutation::CreateIssue {
+            owner,
+            repo,
+            title,
+            body,
+        } => {
+            // Creating an issue isn't idempotent, so don't send it again if it got through
+            if let Some(issue) = find_created_issue(octocrab, entry, login).await? {
+                println!(
+                    "Issue #{} was already created, not sending it again",
+                    issue.number
+                );
+                return Ok(Replayed::Sent(Some(issue)));
+            }
+
+            let sent = with_rate_limit(Resource::Core, || {
+                accepted(async {
+                    octocrab
+                        .issues(owner, repo)
+                        .create(title)
+                        .body(body)
+                        .send()
+                        .await
+                })
+            })
+            .await?;
+
+            Ok(match sent {
+                Ok(issue) => Replayed::Sent(Some(IssueData::from(issue))),
+                Err(reason) => Replayed::Conflict(format!("GitHub rejected the issue: {}", reason)),
+            })
+        }
+    }
+}
+
+/// Reloads the issue a sent write touched, so the cache shows what GitHub has.
+async fn refresh(app: &AppHandle, entry: &OutboxEntry) {
+    let (owner, repo, issue_number) = match &entry.mutation {
+        Mutation::AddComment {
+            owner,
+            repo,
+            issue_number,
+            ..
+        }
+        | Mutation::EditComment {
+            owner,
+            repo,
+            issue_number,
+            ..
+        } => (owner.clone(), repo.clone(), *issue_number),
+        Mutation::CreateIssue { .. } => return,
+    };
+
+    if let Err(e) = fetch_single_issue(
+        app.clone(),
+        owner,
+        repo,
+        issue_number,
+        app.state::<IssuesCache>(),
+    )
+    .await
+    {
+        println!("Failed to refresh issue #{}: {}", issue_number, e);
+    }
+}
+
+fn emit_entries(app: &AppHandle, entries: &[OutboxEntry]) {
+    if let Err(e) = app.emit("outbox-changed", entries) {
+        println!("Failed to emit outbox-changed: {}", e);
+    }
+}
+
+/// Sends queued writes in the order they were made, stopping at the first one that can't
+/// get through. Returns what's left in the queue.
+pub async fn flush(app: &AppHandle) -> Result<Vec<OutboxEntry>, String> {
+    let outbox = app.state::<Outbox>().inner().clone();
+    // Another flush is already working through the queue
+    let Ok(_flushing) = outbox.flushing.try_lock() else {
+        return outbox.entries();
+    };
+    let ids: Vec<i64> = outbox
+        .entries()?
+        .iter()
+        .filter(|entry| entry.conflict.is_none())
+        .map(|entry| entry.id)
+        .collect();
+    if ids.is_empty() {
+        return outbox.entries();
+    }
+
+    let octocrab = get_client()?;
+    let login = get_username(app.clone()).ok();
+    let cache = app.state::<IssuesCache>().inner().clone();
+
+    // Entries are re-read as they go, sending an issue can renumber later comments
+    for id in ids {
+        let Some(mut entry) = outbox.get(id)? else {
+            continue;
+        };
+
+        match replay(&octocrab, &entry, login.as_deref()).await {
+            Ok(Replayed::Sent(created)) => {
+                outbox.remove(entry.id)?;
+                hide_pending(&cache, &entry)?;
+
+                if let Some(issue) = created {
+                    let key = entry.mutation.repo_key();
+                    outbox.renumber(&key, entry.placeholder_id(), issue.number)?;
+                    cache.upsert_issue(&key, issue)?;
+                } else {
+                    refresh(app, &entry).await;
+                }
+            }
+            Ok(Replayed::Conflict(reason)) => {
+                println!("Outbox entry {} conflicts: {}", entry.id, reason);
+                entry.conflict = Some(reason);
+                entry.force = false;
+                outbox.update(&entry)?;
+                if let Err(e) = app.emit("outbox-conflict", &entry) {
+                    println!("Failed to emit outbox-conflict: {}", e);
+                }
+            }
+            Err(e) => {
+                entry.attempts += 1;
+                entry.last_error = Some(e.clone());
+                if entry.attempts >= MAX_ATTEMPTS && is_online().await {
+                    entry.conflict = Some(format!("Failed {} times: {}", entry.attempts, e));
+                    if let Err(e) = app.emit("outbox-conflict", &entry) {
+                        println!("Failed to emit outbox-conflict: {}", e);
+                    }
+                }
+                outbox.update(&entry)?;
+
+                // Later writes wait, so they still reach GitHub in order
+                if entry.conflict.is_none() {
+                    break;
+                }
+            }
+        }
+    }
+
+    let entries = outbox.entries()?;
+    emit_entries(app, &entries);
+    Ok(entries)
+}
+
+/// Sends queued writes once GitHub can be reached again. Runs whether or not background
+/// sync is enabled, which flushes the outbox itself before each sync.
+pub fn start_watcher(app: &AppHandle) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            tokio::time::sleep(std::time::Duration::from_secs(WATCH_INTERVAL_SECS)).await;
+
+            let pending = app.state::<Outbox>().has_pending().unwrap_or(false);
+            if !pending || get_client().is_err() || !is_online().await {
+                continue;
+            }
+            if let Err(e) = flush(&app).await {
+                println!("Failed to send queued writes: {}", e);
+            }
+        }
+    });
+}
+
+#[command]
+pub async fn flush_outbox(app: AppHandle) -> Result<Vec<OutboxEntry>, String> {
+    flush(&app).await
+}
+
+/// Sends a write that was set aside because of a conflict, skipping the conflict checks.
+#[command]
+pub async fn retry_outbox_entry(
+    app: AppHandle,
+    id: i64,
+    outbox: State<'_, Outbox>,
+) -> Result<Vec<OutboxEntry>, String> {
+    let mut entry = outbox
+        .get(id)?
+        .ok_or_else(|| format!("No queued write with id {}", id))?;
+    entry.conflict = None;
+    entry.force = true;
+    entry.attempts = 0;
+    outbox.update(&entry)?;
+
+    flush(&app).await
+}
+
+/// Drops a queued write and takes it back out of the cache.
+#[command]
+pub async fn discard_outbox_entry(
+    app: AppHandle,
+    id: i64,
+    outbox: State<'_, Outbox>,
+    cache: State<'_, IssuesCache>,
+) -> Result<Vec<OutboxEntry>, String> {
+    if let Some(entry) = outbox.remove(id)? {
+        hide_pending(&cache, &entry)?;
+        // Brings back the text a discarded edit replaced
+        if let Mutation::EditComment { .. } = entry.mutation {
+            refresh(&app, &entry).await;
+        }
+    }
+
+    let entries = outbox.entries()?;
+    emit_entries(&app, &entries);
+    Ok(entries)
+}
@@ -0,0 +1,225 @@
+use super::index::tokenize;
+use crate::github::issues::{IssueData, IssuesCache};
+use serde::Serialize;
+use std::collections::HashMap;
+use tauri::{command, State};
+
+/// Titles are short and say what the issue is about, so their terms count more.
+const TITLE_WEIGHT: f64 = 2.0;
+/// Below this cosine similarity issues only share some common words.
+const MIN_SCORE: f64 = 0.2;
+const MAX_CANDIDATES: usize = 5;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DuplicateCandidate {
+    pub number: i64,
+    pub title: String,
+    pub state: String,
+    /// Cosine similarity between 0 and 1.
+    pub score: f64,
+}
+
+fn term_counts(title: &str, body: &str) -> HashMap<String, f64> {
+    let mut counts = HashMap::new();
+    for term in tokenize(title) {
+        *counts.entry(term).or_insert(0.0) += TITLE_WEIGHT;
+    }
+    for term in tokenize(body) {
+        *counts.entry(term).or_insert(0.0) += 1.0;
+    }
+    counts
+}
+
+/// Weighs each term by how often it appears in the text and how rare it is across the
+/// repo, then scales the vector to unit length.
+fn tf_idf(counts: &HashMap<String, f64>, idf: &HashMap<&str, f64>) -> HashMap<String, f64> {
+    let mut vector: HashMap<String, f64> = counts
+        .iter()
+        .map(|(term, count)| {
+            let weight = (1.0 + count.ln()) * idf.get(term.as_str()).copied().unwrap_or(0.0);
+            (term.clone(), weight)
+        })
+        .collect();
+
+    let norm = vector.values().map(|w| w * w).sum::<f64>().sqrt();
+    if norm > 0.0 {
+        vector.values_mut().for_each(|w| *w /= norm);
+    }
+    vector
+}
+
+/// Ranks `issues` by how similar their title and body are to the draft.
+pub fn find_duplicates(title: &str, body: &str, issues: &[IssueData]) -> Vec<DuplicateCandidate> {
+    let issues: Vec<&IssueData> = issues
+        .iter()
+        .filter(|issue| !issue.pending && !issue.is_pull_request)
+        .collect();
+    let draft = term_counts(title, body);
+    if draft.is_empty() || issues.is_empty() {
+        return Vec::new();
+    }
+
+    let docs: Vec<HashMap<String, f64>> = issues
+        .iter()
+        .map(|issue| term_counts(&issue.title, issue.body.as_deref().unwrap_or_default()))
+        .collect();
+
+    // The draft counts as a document too, so its own terms always have a weight
+    let total = (docs.len() + 1) as f64;
+    let mut frequency: HashMap<&str, f64> = HashMap::new();
+    for doc in docs.iter().chain(std::iter::once(&draft)) {
+        for term in doc.keys() {
+            *frequency.entry(term.as_str()).or_insert(0.0) += 1.0;
+        }
+    }
+    let idf: HashMap<&str, f64> = frequency
+        .into_iter()
+        .map(|(term, df)| (term, (total / df).ln() + 1.0))
+        .collect();
+
+    let query = tf_idf(&draft, &idf);
+    let mut candidates: Vec<DuplicateCandidate> = issues
+        .iter()
+        .zip(&docs)
+        .filter_map(|(issue, counts)| {
+            let vector = tf_idf(counts, &idf);
+            let score: f64 = query
+                .iter()
+                .filter_map(|(term, weight)| vector.get(term).map(|other| weight * other))
+                .sum();
+            (score >= MIN_SCORE).then(|| DuplicateCandidate {
+                number: issue.number,
+                title: issue.title.clone(),
+                state: issue.state.clone(),
+                score,
+            })
+        })
+        .collect();
+
+    candidates.sort_by(|a, b| {
+        b.score
+            .partial_cmp(&a.score)
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .then(b.number.cmp(&a.number))
+    });
+    candidates.truncate(MAX_CANDIDATES);
+    candidates
+}
+
+/// Looks for cached issues in the repo that a new issue would likely duplicate, best
+/// match first. Runs locally so it can be called before anything is sent.
+#[command]
+pub async fn find_duplicate_issues(
+    owner: String,
+    repo: String,
+    title: String,
+    body: String,
+    cache: State<'_, IssuesCache>,
+) -> Result<Vec<DuplicateCandidate>, String> {
+    let cache_key = format!("{}/{}", owner, repo);
+    // Score a copy so syncs aren't kept waiting on the cache lock
+    let issues = {
+        let cache_guard = cache.get_cache().lock().map_err(|e| e.to_string())?;
+        match cache_guard.get(&cache_key) {
+            Some(cached) => cached.issues.clone(),
+            None => return Ok(Vec::new()),
+        }
+    };
+    Ok(find_duplicates(&title, &body, &issues))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::github::tasks::TaskList;
+
+    fn issue(number: i64, title: &str, body: &str) -> IssueData {
+        IssueData {
+            number,
+            title: title.to_string(),
+            state: String::from("open"),
+            state_reason: None,
+            created_at: String::from("2024-01-01T00:00:00+00:00"),
+            updated_at: String::from("2024-01-01T00:00:00+00:00"),
+            body: Some(body.to_string()),
+            labels: Vec::new(),
+            assignees: Vec::new(),
+            milestone: None,
+            comments: Vec::new(),
+            comments_count: 0,
+            comments_loaded: true,
+            comments_error: None,
+            creator: String::from("octocat"),
+            is_pull_request: false,
+            reactions: None,
+            pending: false,
+            locked: false,
+            lock_reason: None,
+            pinned: None,
+            tasks: TaskList::default(),
+        }
+    }
+
+    fn repo() -> Vec<IssueData> {
+        vec![
+            issue(
+                1,
+                "App crashes on startup",
+                "Crashing right after the splash screen",
+            ),
+            issue(2, "Add dark mode", "A dark theme for the settings page"),
+            issue(3, "Typo in the readme", "Install is spelled wrong"),
+        ]
+    }
+
+    #[test]
+    fn ranks_the_closest_issue_first() {
+        let candidates = find_duplicates(
+            "Crash on startup",
+            "The app crashed when I opened it",
+            &repo(),
+        );
+        assert_eq!(candidates[0].number, 1);
+        assert!(candidates[0].score > 0.0 && candidates[0].score <= 1.0);
+        assert!(candidates
+            .windows(2)
+            .all(|pair| pair[0].score >= pair[1].score));
+    }
+
+    #[test]
+    fn scores_an_identical_issue_near_one() {
+        let candidates = find_duplicates(
+            "App crashes on startup",
+            "Crashing right after the splash screen",
+            &repo(),
+        );
+        assert_eq!(candidates[0].number, 1);
+        assert!((candidates[0].score - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn drops_issues_below_the_threshold() {
+        let candidates = find_duplicates("Dark mode crashes", "", &repo());
+        assert!(candidates.iter().all(|c| c.score >= MIN_SCORE));
+        assert!(candidates.iter().all(|c| c.number != 3));
+
+        assert!(find_duplicates("Unrelated words entirely", "", &repo()).is_empty());
+    }
+
+    #[test]
+    fn skips_pending_issues_and_pull_requests() {
+        let mut pending = issue(-1, "App crashes on startup", "");
+        pending.pending = true;
+        let mut pull = issue(4, "Fix crash on startup", "");
+        pull.is_pull_request = true;
+        let issues = vec![pending, pull];
+
+        assert!(find_duplicates("App crashes on startup", "", &issues).is_empty());
+    }
+
+    #[test]
+    fn empty_drafts_match_nothing() {
+        assert!(find_duplicates("", "  -- ", &repo()).is_empty());
+        assert!(find_duplicates("App crashes", "", &[]).is_empty());
+    }
+}
@@ -0,0 +1,402 @@
+use crate::github::issues::{IssueData, IssuesCache};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use tauri::{command, State};
+
+const TITLE_WEIGHT: f64 = 3.0;
+const BM25_K1: f64 = 1.2;
+const BM25_B: f64 = 0.75;
+const SNIPPET_RADIUS: usize = 60;
+
+type DocKey = (String, i64);
+
+/// Inverted index over the titles, bodies and comments of every cached issue.
+#[derive(Debug, Default)]
+pub struct SearchIndex {
+    docs: HashMap<DocKey, IndexedIssue>,
+    postings: HashMap<String, HashMap<DocKey, f64>>,
+    total_length: f64,
+}
+
+#[derive(Debug)]
+struct IndexedIssue {
+    title: String,
+    state: String,
+    is_pull_request: bool,
+    fields: Vec<IndexedField>,
+    terms: HashSet<String>,
+    length: f64,
+}
+
+#[derive(Debug)]
+struct IndexedField {
+    kind: MatchField,
+    comment_id: Option<i64>,
+    text: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchField {
+    Title,
+    Body,
+    Comment,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SnippetPart {
+    pub text: String,
+    pub highlight: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchResult {
+    pub repo: String,
+    pub number: i64,
+    pub title: String,
+    pub state: String,
+    pub is_pull_request: bool,
+    pub score: f64,
+    pub matched_in: MatchField,
+    pub comment_id: Option<i64>,
+    pub snippet: Vec<SnippetPart>,
+}
+
+/// Lowercases and splits on anything that isn't a letter or digit, keeping byte offsets
+/// into the original text so matches can be highlighted.
+fn tokenize_with_offsets(text: &str) -> Vec<(usize, usize, String)> {
+    let mut tokens = Vec::new();
+    let mut start = None;
+
+    for (offset, ch) in text
+        .char_indices()
+        .chain(std::iter::once((text.len(), ' ')))
+    {
+        match (ch.is_alphanumeric(), start) {
+            (true, None) => start = Some(offset),
+            (false, Some(token_start)) => {
+                let token = &text[token_start..offset];
+                if token.chars().count() > 1 {
+                    tokens.push((token_start, offset, stem(&token.to_lowercase())));
+                }
+                start = None;
+            }
+            _ => {}
+        }
+    }
+
+    tokens
+}
+
+pub fn tokenize(text: &str) -> Vec<String> {
+    tokenize_with_offsets(text)
+        .into_iter()
+        .map(|(_, _, token)| token)
+        .collect()
+}
+
+/// A light suffix stemmer so "crash", "crashes" and "crashing" land on the same term. A final
+/// "e" goes too, so "issue" meets "issues" and "release" meets "released".
+fn stem(word: &str) -> String {
+    let mut stem = word;
+    for suffix in ["ing", "ed", "es", "s"] {
+        if let Some(stripped) = word.strip_suffix(suffix) {
+            if stripped.chars().count() >= 3 && !word.ends_with("ss") {
+                stem = stripped;
+                break;
+            }
+        }
+    }
+
+    match stem.strip_suffix('e') {
+        Some(stripped) if stripped.chars().count() >= 3 => stripped.to_string(),
+        _ => stem.to_string(),
+    }
+}
+
+impl SearchIndex {
+    pub fn index_repo(&mut self, repo: &str, issues: &[IssueData]) {
+        let stale: Vec<DocKey> = self
+            .docs
+            .keys()
+            .filter(|(doc_repo, _)| doc_repo == repo)
+            .cloned()
+            .collect();
+        for key in stale {
+            self.remove(&key);
+        }
+
+        for issue in issues {
+            self.index_issue(repo, issue);
+        }
+    }
+
+    pub fn index_issue(&mut self, repo: &str, issue: &IssueData) {
+        let key = (repo.to_string(), issue.number);
+        self.remove(&key);
+
+        let mut fields = vec![IndexedField {
+            kind: MatchField::Title,
+            comment_id: None,
+            text: issue.title.clone(),
+        }];
+        if let Some(body) = issue.body.as_ref().filter(|body| !body.is_empty()) {
+            fields.push(IndexedField {
+                kind: MatchField::Body,
+                comment_id: None,
+                text: body.clone(),
+            });
+        }
+        for comment in &issue.comments {
+            fields.push(IndexedField {
+                kind: MatchField::Comment,
+                comment_id: Some(comment.id),
+                text: comment.body.clone(),
+            });
+        }
+
+        let mut frequencies: HashMap<String, f64> = HashMap::new();
+        let mut length = 0.0;
+        for field in &fields {
+            let weight = if field.kind == MatchField::Title {
+                TITLE_WEIGHT
+            } else {
+                1.0
+            };
+            for term in tokenize(&field.text) {
+                *frequencies.entry(term).or_default() += weight;
+                length += 1.0;
+            }
+        }
+
+        for (term, frequency) in &frequencies {
+            self.postings
+                .entry(term.clone())
+                .or_default()
+                .insert(key.clone(), *frequency);
+        }
+
+        self.total_length += length;
+        self.docs.insert(
+            key,
+            IndexedIssue {
+                title: issue.title.clone(),
+                state: issue.state.clone(),
+                is_pull_request: issue.is_pull_request,
+                fields,
+                terms: frequencies.into_keys().collect(),
+                length,
+            },
+        );
+    }
+
+    pub fn remove(&mut self, key: &DocKey) {
+        let Some(doc) = self.docs.remove(key) else {
+            return;
+        };
+
+        self.total_length -= doc.length;
+        for term in doc.terms {
+            if let Some(postings) = self.postings.get_mut(&term) {
+                postings.remove(key);
+                if postings.is_empty() {
+                    self.postings.remove(&term);
+                }
+            }
+        }
+    }
+
+    /// Ranks issues with BM25, optionally limited to some repos.
+    pub fn search(&self, query: &str, repos: Option<&[String]>, limit: usize) -> Vec<SearchResult> {
+        let terms: HashSet<String> = tokenize(query).into_iter().collect();
+        if terms.is_empty() || self.docs.is_empty() {
+            return Vec::new();
+        }
+
+        let doc_count = self.docs.len() as f64;
+        let average_length = (self.total_length / doc_count).max(1.0);
+
+        let mut scores: HashMap<&DocKey, f64> = HashMap::new();
+        for term in &terms {
+            let Some(postings) = self.postings.get(term) else {
+                continue;
+            };
+
+            let matching = postings.len() as f64;
+            let idf = ((doc_count - matching + 0.5) / (matching + 0.5) + 1.0).ln();
+
+            for (key, frequency) in postings {
+                if let Some(repos) = repos {
+                    if !repos.contains(&key.0) {
+                        continue;
+                    }
+                }
+
+                let length = self.docs[key].length;
+                let norm = BM25_K1 * (1.0 - BM25_B + BM25_B * length / average_length);
+                *scores.entry(key).or_default() +=
+                    idf * frequency * (BM25_K1 + 1.0) / (frequency + norm);
+            }
+        }
+
+        let mut ranked: Vec<(&DocKey, f64)> = scores.into_iter().collect();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+        ranked.truncate(limit);
+
+        ranked
+            .into_iter()
+            .map(|(key, score)| {
+                let doc = &self.docs[key];
+                let (field, snippet) = best_snippet(doc, &terms);
+
+                SearchResult {
+                    repo: key.0.clone(),
+                    number: key.1,
+                    title: doc.title.clone(),
+                    state: doc.state.clone(),
+                    is_pull_request: doc.is_pull_request,
+                    score,
+                    matched_in: field.kind,
+                    comment_id: field.comment_id,
+                    snippet,
+                }
+            })
+            .collect()
+    }
+}
+
+/// Picks the field with the most matching terms and cuts a highlighted window around the first hit.
+fn best_snippet<'a>(
+    doc: &'a IndexedIssue,
+    terms: &HashSet<String>,
+) -> (&'a IndexedField, Vec<SnippetPart>) {
+    let mut best: Option<(&IndexedField, Vec<(usize, usize)>)> = None;
+    for field in &doc.fields {
+        let hits: Vec<(usize, usize)> = tokenize_with_offsets(&field.text)
+            .into_iter()
+            .filter(|(_, _, token)| terms.contains(token))
+            .map(|(start, end, _)| (start, end))
+            .collect();
+
+        // Prefer the title on ties, then whichever field matched more
+        if best
+            .as_ref()
+            .is_none_or(|(_, most)| hits.len() > most.len())
+        {
+            best = Some((field, hits));
+        }
+    }
+
+    let (field, hits) = best.unwrap_or((&doc.fields[0], Vec::new()));
+    let text = field.text.as_str();
+
+    let first = hits.first().map(|(start, _)| *start).unwrap_or(0);
+    let window_start = floor_char_boundary(text, first.saturating_sub(SNIPPET_RADIUS));
+    let window_end = floor_char_boundary(text, (first + SNIPPET_RADIUS * 2).min(text.len()));
+
+    let mut parts = Vec::new();
+    let mut cursor = window_start;
+    if window_start > 0 {
+        parts.push(SnippetPart {
+            text: "…".to_string(),
+            highlight: false,
+        });
+    }
+    for (start, end) in hits
+        .into_iter()
+        .filter(|(start, end)| *start >= window_start && *end <= window_end)
+    {
+        if start > cursor {
+            parts.push(SnippetPart {
+                text: text[cursor..start].to_string(),
+                highlight: false,
+            });
+        }
+        parts.push(SnippetPart {
+            text: text[start..end].to_string(),
+            highlight: true,
+        });
+        cursor = end;
+    }
+    if cursor < window_end {
+        parts.push(SnippetPart {
+            text: text[cursor..window_end].to_string(),
+            highlight: false,
+        });
+    }
+    if window_end < text.len() {
+        parts.push(SnippetPart {
+            text: "…".to_string(),
+            highlight: false,
+        });
+    }
+
+    (field, parts)
+}
+
+fn floor_char_boundary(text: &str, mut index: usize) -> usize {
+    while !text.is_char_boundary(index) {
+        index -= 1;
+    }
+    index
+}
+
+#[command]
+pub async fn search_issues(
+    query: String,
+    repos: Option<Vec<String>>,
+    limit: Option<usize>,
+    cache: State<'_, IssuesCache>,
+) -> Result<Vec<SearchResult>, String> {
+    let index = cache.search_index().lock().map_err(|e| e.to_string())?;
+    Ok(index.search(&query, repos.as_deref(), limit.unwrap_or(50)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn same_term(a: &str, b: &str) {
+        assert_eq!(stem(a), stem(b), "{} and {} should stem alike", a, b);
+    }
+
+    #[test]
+    fn stems_plurals_and_tenses_together() {
+        same_term("issue", "issues");
+        same_term("file", "files");
+        same_term("release", "released");
+        same_term("release", "releasing");
+        same_term("crash", "crashes");
+        same_term("crash", "crashed");
+        same_term("crash", "crashing");
+        same_term("fix", "fixes");
+    }
+
+    #[test]
+    fn leaves_short_words_and_double_s_alone() {
+        assert_eq!(stem("use"), "use");
+        assert_eq!(stem("uses"), "use");
+        assert_eq!(stem("the"), "the");
+        assert_eq!(stem("class"), "class");
+        assert_eq!(stem("pass"), "pass");
+    }
+
+    #[test]
+    fn tokenizes_lowercased_words_of_two_or_more_characters() {
+        assert_eq!(
+            tokenize("App CRASHES on start-up, see #12 (a)"),
+            vec!["app", "crash", "on", "start", "up", "see", "12"]
+        );
+        assert!(tokenize("  -- ").is_empty());
+    }
+
+    #[test]
+    fn keeps_byte_offsets_of_the_original_text() {
+        let text = "Ünicode issues";
+        let tokens = tokenize_with_offsets(text);
+        assert_eq!(tokens.len(), 2);
+        let (start, end, token) = &tokens[1];
+        assert_eq!(&text[*start..*end], "issues");
+        assert_eq!(token, "issu");
+    }
+}
@@ -0,0 +1,3 @@
+pub mod duplicates;
+pub mod index;
+pub mod query;
@@ -0,0 +1,493 @@
+use super::index::tokenize;
+use crate::github::issues::{CachedIssues, IssueData, IssuesCache, ReactionSummary};
+use crate::github::oauth::get_username;
+use chrono::{DateTime, NaiveDate};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::ops::Bound;
+use tauri::{command, AppHandle, State};
+
+/// A parsed GitHub-style search such as `is:open label:bug -label:wontfix crash`.
+#[derive(Debug, Clone, Default)]
+pub struct IssueQuery {
+    qualifiers: Vec<Qualifier>,
+    text: Vec<String>,
+    // Words of each `-word` or `-"some phrase"`, an issue containing all of one is left out
+    excluded: Vec<Vec<String>>,
+    text_in: HashSet<TextField>,
+    sort: SortKey,
+    ascending: bool,
+}
+
+#[derive(Debug, Clone)]
+struct Qualifier {
+    negated: bool,
+    predicate: Predicate,
+}
+
+#[derive(Debug, Clone)]
+enum Predicate {
+    State(String),
+    PullRequest(bool),
+    Reason(String),
+    // Comma separated values match any of them, like on GitHub
+    Label(Vec<String>),
+    Author(String),
+    Assignee(String),
+    Milestone(String),
+    Repo(String),
+    Missing(MissingField),
+    Created(Range<NaiveDate>),
+    Updated(Range<NaiveDate>),
+    Comments(Range<i64>),
+    Reactions(Range<i64>),
+}
+
+#[derive(Debug, Clone, Copy)]
+enum MissingField {
+    Label,
+    Assignee,
+    Milestone,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum TextField {
+    Title,
+    Body,
+    Comments,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+enum SortKey {
+    #[default]
+    Created,
+    Updated,
+    Comments,
+    Reactions,
+    // `reactions-+1`, the thumbs-up votes used to rank feature requests
+    ThumbsUp,
+}
+
+#[derive(Debug, Clone)]
+struct Range<T> {
+    lower: Bound<T>,
+    upper: Bound<T>,
+}
+
+impl<T: PartialOrd> Range<T> {
+    fn contains(&self, value: &T) -> bool {
+        let above = match &self.lower {
+            Bound::Included(lower) => value >= lower,
+            Bound::Excluded(lower) => value > lower,
+            Bound::Unbounded => true,
+        };
+        let below = match &self.upper {
+            Bound::Included(upper) => value <= upper,
+            Bound::Excluded(upper) => value < upper,
+            Bound::Unbounded => true,
+        };
+        above && below
+    }
+}
+
+/// Parses `>x`, `>=x`, `<x`, `<=x`, `x..y` (either side may be `*`) or a plain `x`.
+fn parse_range<T: Clone>(value: &str, parse: impl Fn(&str) -> Option<T>) -> Option<Range<T>> {
+    let bound = |value: &str| -> Option<Bound<T>> {
+        if value == "*" {
+            Some(Bound::Unbounded)
+        } else {
+            parse(value).map(Bound::Included)
+        }
+    };
+
+    let (lower, upper) = if let Some(rest) = value.strip_prefix(">=") {
+        (Bound::Included(parse(rest)?), Bound::Unbounded)
+    } else if let Some(rest) = value.strip_prefix('>') {
+        (Bound::Excluded(parse(rest)?), Bound::Unbounded)
+    } else if let Some(rest) = value.strip_prefix("<=") {
+        (Bound::Unbounded, Bound::Included(parse(rest)?))
+    } else if let Some(rest) = value.strip_prefix('<') {
+        (Bound::Unbounded, Bound::Excluded(parse(rest)?))
+    } else if let Some((from, to)) = value.split_once("..") {
+        (bound(from)?, bound(to)?)
+    } else {
+        let exact = parse(value)?;
+        (Bound::Included(exact.clone()), Bound::Included(exact))
+    };
+
+    Some(Range { lower, upper })
+}
+
+fn parse_date(value: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(value, "%Y-%m-%d").ok()
+}
+
+fn issue_date(timestamp: &str) -> Option<NaiveDate> {
+    DateTime::parse_from_rfc3339(timestamp)
+        .ok()
+        .map(|t| t.date_naive())
+}
+
+/// Splits on whitespace while keeping quoted values such as `label:"good first issue"` together.
+fn split_terms(query: &str) -> Vec<String> {
+    let mut terms = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+
+    for ch in query.chars() {
+        match ch {
+            '"' => quoted = !quoted,
+            ch if ch.is_whitespace() && !quoted => {
+                if !current.is_empty() {
+                    terms.push(std::mem::take(&mut current));
+                }
+            }
+            ch => current.push(ch),
+        }
+    }
+    if !current.is_empty() {
+        terms.push(current);
+    }
+
+    terms
+}
+
+impl IssueQuery {
+    pub fn parse(query: &str) -> Result<Self, String> {
+        let mut parsed = IssueQuery::default();
+
+        for term in split_terms(query) {
+            let (negated, body) = match term.strip_prefix('-') {
+                Some(rest) if !rest.is_empty() => (true, rest),
+                _ => (false, term.as_str()),
+            };
+
+            let Some((key, value)) = body.split_once(':').filter(|(_, value)| !value.is_empty())
+            else {
+                parsed.add_text(negated, body);
+                continue;
+            };
+            let key = key.to_lowercase();
+            let invalid = || format!("Invalid value for {}: {}", key, value);
+
+            let predicate = match key.as_str() {
+                "is" | "state" | "type" => match value.to_lowercase().as_str() {
+                    "open" | "closed" => Predicate::State(value.to_lowercase()),
+                    "issue" => Predicate::PullRequest(false),
+                    "pr" | "pull-request" => Predicate::PullRequest(true),
+                    _ => return Err(invalid()),
+                },
+                "reason" => Predicate::Reason(value.to_lowercase().replace([' ', '-'], "_")),
+                "label" => Predicate::Label(
+                    value
+                        .split(',')
+                        .map(|label| label.trim().to_lowercase())
+                        .collect(),
+                ),
+                "author" => Predicate::Author(value.to_string()),
+                "assignee" => Predicate::Assignee(value.to_string()),
+                "milestone" => Predicate::Milestone(value.to_lowercase()),
+                "repo" => Predicate::Repo(value.to_lowercase()),
+                "no" => Predicate::Missing(match value.to_lowercase().as_str() {
+                    "label" => MissingField::Label,
+                    "assignee" => MissingField::Assignee,
+                    "milestone" => MissingField::Milestone,
+                    _ => return Err(invalid()),
+                }),
+                "created" => {
+                    Predicate::Created(parse_range(value, parse_date).ok_or_else(invalid)?)
+                }
+                "updated" => {
+                    Predicate::Updated(parse_range(value, parse_date).ok_or_else(invalid)?)
+                }
+                "comments" => {
+                    Predicate::Comments(parse_range(value, |v| v.parse().ok()).ok_or_else(invalid)?)
+                }
+                "reactions" => Predicate::Reactions(
+                    parse_range(value, |v| v.parse().ok()).ok_or_else(invalid)?,
+                ),
+                "in" => {
+                    for field in value.split(',') {
+                        parsed.text_in.insert(match field.to_lowercase().as_str() {
+                            "title" => TextField::Title,
+                            "body" => TextField::Body,
+                            "comments" => TextField::Comments,
+                            _ => return Err(invalid()),
+                        });
+                    }
+                    continue;
+                }
+                "sort" => {
+                    let value = value.to_lowercase();
+                    let (field, ascending) = match value.rsplit_once('-') {
+                        Some((field, "asc")) => (field, true),
+                        Some((field, "desc")) => (field, false),
+                        _ => (value.as_str(), false),
+                    };
+                    parsed.sort = match field {
+                        "created" => SortKey::Created,
+                        "updated" => SortKey::Updated,
+                        "comments" => SortKey::Comments,
+                        "reactions" => SortKey::Reactions,
+                        "reactions-+1" => SortKey::ThumbsUp,
+                        _ => return Err(invalid()),
+                    };
+                    parsed.ascending = ascending;
+                    continue;
+                }
+                // Unknown qualifiers are searched as text, the same as GitHub does
+                _ => {
+                    parsed.add_text(negated, body);
+                    continue;
+                }
+            };
+
+            parsed.qualifiers.push(Qualifier { negated, predicate });
+        }
+
+        Ok(parsed)
+    }
+
+    fn add_text(&mut self, negated: bool, text: &str) {
+        let words = tokenize(text);
+        if !negated {
+            self.text.extend(words);
+        } else if !words.is_empty() {
+            self.excluded.push(words);
+        }
+    }
+
+    /// `me` is the signed-in user's login, used to resolve `@me`.
+    pub fn matches(&self, repo: &str, issue: &IssueData, me: Option<&str>) -> bool {
+        self.qualifiers.iter().all(|qualifier| {
+            qualifier.negated != predicate_matches(&qualifier.predicate, repo, issue, me)
+        }) && self.text_matches(issue)
+    }
+
+    fn text_matches(&self, issue: &IssueData) -> bool {
+        if self.text.is_empty() && self.excluded.is_empty() {
+            return true;
+        }
+
+        let searched = |field: TextField| self.text_in.is_empty() || self.text_in.contains(&field);
+        let mut words: HashSet<String> = HashSet::new();
+        if searched(TextField::Title) {
+            words.extend(tokenize(&issue.title));
+        }
+        if searched(TextField::Body) {
+            words.extend(tokenize(issue.body.as_deref().unwrap_or_default()));
+        }
+        if searched(TextField::Comments) {
+            for comment in &issue.comments {
+                words.extend(tokenize(&comment.body));
+            }
+        }
+
+        self.words_match(&words)
+    }
+
+    fn words_match(&self, words: &HashSet<String>) -> bool {
+        self.text.iter().all(|term| words.contains(term))
+            && !self
+                .excluded
+                .iter()
+                .any(|phrase| phrase.iter().all(|term| words.contains(term)))
+    }
+
+    pub fn sort(&self, issues: &mut [RepoIssue]) {
+        issues.sort_by(|a, b| {
+            let ordering = match self.sort {
+                SortKey::Created => a.issue.created_at.cmp(&b.issue.created_at),
+                SortKey::Updated => a.issue.updated_at.cmp(&b.issue.updated_at),
+                SortKey::Comments => a.issue.comments_count.cmp(&b.issue.comments_count),
+                SortKey::Reactions => reaction_count(&a.issue, |r| r.total_count)
+                    .cmp(&reaction_count(&b.issue, |r| r.total_count)),
+                SortKey::ThumbsUp => reaction_count(&a.issue, |r| r.plus_one)
+                    .cmp(&reaction_count(&b.issue, |r| r.plus_one)),
+            };
+            if self.ascending {
+                ordering
+            } else {
+                ordering.reverse()
+            }
+        });
+    }
+
+    /// Runs the query over the cached issues of the given repos, or every cached repo when empty.
+    pub fn run(
+        &self,
+        cached: &HashMap<String, CachedIssues>,
+        repos: &[String],
+        me: Option<&str>,
+    ) -> Vec<RepoIssue> {
+        let mut results: Vec<RepoIssue> = cached
+            .iter()
+            .filter(|(key, _)| repos.is_empty() || repos.contains(key))
+            .flat_map(|(key, cached)| {
+                cached
+                    .issues
+                    .iter()
+                    .filter(|issue| self.matches(key, issue, me))
+                    .map(|issue| RepoIssue {
+                        repo: key.clone(),
+                        issue: issue.clone(),
+                    })
+            })
+            .collect();
+
+        self.sort(&mut results);
+        results
+    }
+}
+
+fn reaction_count(issue: &IssueData, count: impl Fn(&ReactionSummary) -> i64) -> i64 {
+    issue.reactions.as_ref().map(count).unwrap_or_default()
+}
+
+fn same_user(login: &str, wanted: &str, me: Option<&str>) -> bool {
+    match wanted {
+        "@me" => me.is_some_and(|me| me.eq_ignore_ascii_case(login)),
+        wanted => wanted.eq_ignore_ascii_case(login),
+    }
+}
+
+fn predicate_matches(
+    predicate: &Predicate,
+    repo: &str,
+    issue: &IssueData,
+    me: Option<&str>,
+) -> bool {
+    match predicate {
+        Predicate::State(state) => issue.state == *state,
+        Predicate::PullRequest(is_pull_request) => issue.is_pull_request == *is_pull_request,
+        Predicate::Reason(reason) => issue.state_reason.as_deref() == Some(reason.as_str()),
+        Predicate::Label(labels) => issue
+            .labels
+            .iter()
+            .any(|label| labels.contains(&label.to_lowercase())),
+        Predicate::Author(author) => same_user(&issue.creator, author, me),
+        Predicate::Assignee(assignee) => issue
+            .assignees
+            .iter()
+            .any(|login| same_user(login, assignee, me)),
+        Predicate::Milestone(title) => issue
+            .milestone
+            .as_ref()
+            .is_some_and(|milestone| milestone.title.to_lowercase() == *title),
+        Predicate::Repo(name) => repo.to_lowercase() == *name,
+        Predicate::Missing(MissingField::Label) => issue.labels.is_empty(),
+        Predicate::Missing(MissingField::Assignee) => issue.assignees.is_empty(),
+        Predicate::Missing(MissingField::Milestone) => issue.milestone.is_none(),
+        Predicate::Created(range) => {
+            issue_date(&issue.created_at).is_some_and(|d| range.contains(&d))
+        }
+        Predicate::Updated(range) => {
+            issue_date(&issue.updated_at).is_some_and(|d| range.contains(&d))
+        }
+        Predicate::Comments(range) => range.contains(&issue.comments_count),
+        Predicate::Reactions(range) => range.contains(&reaction_count(issue, |r| r.total_count)),
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RepoIssue {
+    pub repo: String,
+    #[serde(flatten)]
+    pub issue: IssueData,
+}
+
+#[command]
+pub async fn query_issues(
+    app: AppHandle,
+    query: String,
+    repos: Option<Vec<String>>,
+    cache: State<'_, IssuesCache>,
+) -> Result<Vec<RepoIssue>, String> {
+    let query = IssueQuery::parse(&query)?;
+    // Without a signed-in user `@me` simply matches nothing
+    let me = get_username(app).ok();
+
+    let cache_guard = cache.get_cache().lock().map_err(|e| e.to_string())?;
+    Ok(query.run(&cache_guard, &repos.unwrap_or_default(), me.as_deref()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(text: &str) -> HashSet<String> {
+        tokenize(text).into_iter().collect()
+    }
+
+    #[test]
+    fn negated_words_are_excluded_not_searched() {
+        let query = IssueQuery::parse("-crash login").unwrap();
+        assert_eq!(query.text, vec!["login"]);
+        assert_eq!(query.excluded, vec![vec!["crash"]]);
+
+        assert!(query.words_match(&words("Login fails")));
+        assert!(!query.words_match(&words("Login crashes the app")));
+    }
+
+    #[test]
+    fn negated_phrases_need_every_word() {
+        let query = IssueQuery::parse(r#"-"dark mode""#).unwrap();
+        assert_eq!(query.excluded, vec![vec!["dark", "mod"]]);
+
+        assert!(query.words_match(&words("Dark theme")));
+        assert!(!query.words_match(&words("Add a dark mode")));
+    }
+
+    #[test]
+    fn negated_qualifiers_stay_qualifiers() {
+        let query = IssueQuery::parse("-label:wontfix is:open").unwrap();
+        assert!(query.text.is_empty() && query.excluded.is_empty());
+        assert_eq!(query.qualifiers.len(), 2);
+        assert!(query.qualifiers[0].negated);
+        assert!(
+            matches!(&query.qualifiers[0].predicate, Predicate::Label(labels) if labels == &["wontfix"])
+        );
+        assert!(!query.qualifiers[1].negated);
+        assert!(
+            matches!(&query.qualifiers[1].predicate, Predicate::State(state) if state == "open")
+        );
+    }
+
+    #[test]
+    fn unknown_qualifiers_are_text() {
+        let query = IssueQuery::parse("foo:bar -baz:qux").unwrap();
+        assert_eq!(query.text, vec!["foo", "bar"]);
+        assert_eq!(query.excluded, vec![vec!["baz", "qux"]]);
+        assert!(query.qualifiers.is_empty());
+    }
+
+    #[test]
+    fn a_lone_dash_is_ignored() {
+        let query = IssueQuery::parse("- crash").unwrap();
+        assert_eq!(query.text, vec!["crash"]);
+        assert!(query.excluded.is_empty());
+    }
+
+    #[test]
+    fn quoted_values_and_ranges() {
+        let query =
+            IssueQuery::parse(r#"label:"good first issue" comments:>=3 sort:updated-asc"#).unwrap();
+        assert!(matches!(
+            &query.qualifiers[0].predicate,
+            Predicate::Label(labels) if labels == &["good first issue"]
+        ));
+        let Predicate::Comments(range) = &query.qualifiers[1].predicate else {
+            panic!("expected a comments range");
+        };
+        assert!(range.contains(&3) && !range.contains(&2));
+        assert_eq!(query.sort, SortKey::Updated);
+        assert!(query.ascending);
+    }
+
+    #[test]
+    fn rejects_invalid_values() {
+        assert!(IssueQuery::parse("is:maybe").is_err());
+        assert!(IssueQuery::parse("created:yesterday").is_err());
+        assert!(IssueQuery::parse("sort:stars").is_err());
+    }
+}
@@ -0,0 +1,119 @@
+use rusqlite::Connection;
+
+/// Schema migrations, applied in order. The index of the last applied
+/// migration is kept in SQLite's `user_version` pragma.
+const MIGRATIONS: &[&str] = &[
+    // 1: issues, comments, labels, assignees and per-repo sync metadata
+    r#"
+    CREATE TABLE repos (
+        key TEXT PRIMARY KEY,
+        last_synced TEXT NOT NULL,
+        last_changed TEXT NOT NULL,
+        loaded TEXT NOT NULL,
+        truncated INTEGER NOT NULL DEFAULT 0,
+        validator_route TEXT,
+        etag TEXT,
+        last_modified TEXT
+    );
+
+    CREATE TABLE issues (
+        repo TEXT NOT NULL,
+        number INTEGER NOT NULL,
+        title TEXT NOT NULL,
+        state TEXT NOT NULL,
+        created_at TEXT NOT NULL,
+        updated_at TEXT NOT NULL,
+        body TEXT,
+        creator TEXT NOT NULL,
+        PRIMARY KEY (repo, number)
+    );
+
+    CREATE TABLE issue_labels (
+        repo TEXT NOT NULL,
+        issue_number INTEGER NOT NULL,
+        position INTEGER NOT NULL,
+        name TEXT NOT NULL,
+        PRIMARY KEY (repo, issue_number, position)
+    );
+
+    CREATE TABLE issue_assignees (
+        repo TEXT NOT NULL,
+        issue_number INTEGER NOT NULL,
+        position INTEGER NOT NULL,
+        login TEXT NOT NULL,
+        PRIMARY KEY (repo, issue_number, position)
+    );
+
+    CREATE TABLE comments (
+        repo TEXT NOT NULL,
+        issue_number INTEGER NOT NULL,
+        id INTEGER NOT NULL,
+        position INTEGER NOT NULL,
+        body TEXT NOT NULL,
+        created_at TEXT NOT NULL,
+        updated_at TEXT,
+        author TEXT NOT NULL,
+        PRIMARY KEY (repo, issue_number, position)
+    );
+    "#,
+    // 2: pull requests come back from the issues endpoint too
+    r#"
+    ALTER TABLE issues ADD COLUMN is_pull_request INTEGER NOT NULL DEFAULT 0;
+    -- Existing rows can't be told apart, so reload every repo in full on the next sync
+    UPDATE repos SET truncated = 1, validator_route = NULL, etag = NULL, last_modified = NULL;
+    "#,
+    // 3: why an issue was closed
+    r#"
+    ALTER TABLE issues ADD COLUMN state_reason TEXT;
+    "#,
+    // 4: the issue's milestone, stored as JSON
+    r#"
+    ALTER TABLE issues ADD COLUMN milestone TEXT;
+    UPDATE repos SET truncated = 1, validator_route = NULL, etag = NULL, last_modified = NULL;
+    "#,
+    // 5: comments load separately from the issue list
+    r#"
+    ALTER TABLE issues ADD COLUMN comments_count INTEGER NOT NULL DEFAULT 0;
+    ALTER TABLE issues ADD COLUMN comments_loaded INTEGER NOT NULL DEFAULT 0;
+    ALTER TABLE issues ADD COLUMN comments_error TEXT;
+    UPDATE issues SET comments_count = (
+        SELECT COUNT(*) FROM comments
+        WHERE comments.repo = issues.repo AND comments.issue_number = issues.number
+    );
+    UPDATE repos SET truncated = 1, validator_route = NULL, etag = NULL, last_modified = NULL;
+    "#,
+    // 6: reaction counts from the GraphQL sync, stored as JSON
+    r#"
+    ALTER TABLE issues ADD COLUMN reactions TEXT;
+    ALTER TABLE comments ADD COLUMN reactions TEXT;
+    "#,
+    // 7: offline writes waiting in the outbox
+    r#"
+    ALTER TABLE issues ADD COLUMN pending INTEGER NOT NULL DEFAULT 0;
+    ALTER TABLE comments ADD COLUMN pending INTEGER NOT NULL DEFAULT 0;
+    "#,
+    // 8: conversation locks and pinned issues
+    r#"
+    ALTER TABLE issues ADD COLUMN locked INTEGER NOT NULL DEFAULT 0;
+    ALTER TABLE issues ADD COLUMN lock_reason TEXT;
+    ALTER TABLE issues ADD COLUMN pinned INTEGER;
+    "#,
+    // 9: where the next incremental sync starts, NULL reloads the repo once
+    r#"
+    ALTER TABLE repos ADD COLUMN listed_until TEXT;
+    "#,
+];
+
+pub fn run_migrations(conn: &mut Connection) -> rusqlite::Result<()> {
+    let current: usize = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(current) {
+        println!("Applying database migration {}", index + 1);
+        let tx = conn.transaction()?;
+        tx.execute_batch(migration)?;
+        tx.pragma_update(None, "user_version", index + 1)?;
+        tx.commit()?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,2 @@
+pub mod migrations;
+pub mod store;
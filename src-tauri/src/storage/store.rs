@@ -0,0 +1,375 @@
+use super::migrations::run_migrations;
+use crate::github::issues::{CachedIssues, CommentData, IssueData, IssueStateFilter, Validators};
+use crate::github::tasks::parse_tasks;
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Connection, Transaction};
+use serde::{de::DeserializeOwned, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Mutex;
+
+/// On-disk copy of `IssuesCache`, so issues survive restarts and can be read offline.
+#[derive(Debug)]
+pub struct IssueStore {
+    conn: Mutex<Connection>,
+}
+
+impl IssueStore {
+    pub fn open(path: &Path) -> Result<Self, String> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create data directory: {}", e))?;
+        }
+
+        let mut conn =
+            Connection::open(path).map_err(|e| format!("Failed to open database: {}", e))?;
+        run_migrations(&mut conn).map_err(|e| format!("Failed to migrate database: {}", e))?;
+
+        Ok(Self {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    pub fn load_all(&self) -> Result<HashMap<String, CachedIssues>, String> {
+        let conn = self.conn.lock().map_err(|e| e.to_string())?;
+        load_repos(&conn).map_err(|e| format!("Failed to load issues: {}", e))
+    }
+
+    /// Replaces everything stored for a repo with the given cache entry.
+    pub fn save_repo(&self, key: &str, cached: &CachedIssues) -> Result<(), String> {
+        let mut conn = self.conn.lock().map_err(|e| e.to_string())?;
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+
+        write_repo(&tx, key, cached)
+            .and_then(|_| tx.commit())
+            .map_err(|e| format!("Failed to save issues for {}: {}", key, e))
+    }
+
+    /// Records a sync that found nothing new, without rewriting the repo's issues.
+    pub fn touch_repo(&self, key: &str, last_synced: DateTime<Utc>) -> Result<(), String> {
+        let conn = self.conn.lock().map_err(|e| e.to_string())?;
+        conn.execute(
+            "UPDATE repos SET last_synced = ?2 WHERE key = ?1",
+            params![key, last_synced.to_rfc3339()],
+        )
+        .map(|_| ())
+        .map_err(|e| format!("Failed to update sync time for {}: {}", key, e))
+    }
+
+    /// Inserts or replaces a single issue, creating the repo entry if it's missing.
+    pub fn save_issue(
+        &self,
+        key: &str,
+        cached: &CachedIssues,
+        issue: &IssueData,
+    ) -> Result<(), String> {
+        let mut conn = self.conn.lock().map_err(|e| e.to_string())?;
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+
+        write_repo_metadata(&tx, key, cached)
+            .and_then(|_| write_issue(&tx, key, issue))
+            .and_then(|_| tx.commit())
+            .map_err(|e| format!("Failed to save issue #{} for {}: {}", issue.number, key, e))
+    }
+
+    /// Removes a single issue with its labels, assignees and comments.
+    pub fn delete_issue(&self, key: &str, number: i64) -> Result<(), String> {
+        let mut conn = self.conn.lock().map_err(|e| e.to_string())?;
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+
+        delete_issue_rows(&tx, key, number)
+            .and_then(|_| tx.commit())
+            .map_err(|e| format!("Failed to delete issue #{} for {}: {}", number, key, e))
+    }
+}
+
+fn delete_issue_rows(tx: &Transaction, key: &str, number: i64) -> rusqlite::Result<()> {
+    tx.execute(
+        "DELETE FROM issues WHERE repo = ?1 AND number = ?2",
+        params![key, number],
+    )?;
+    for table in ["issue_labels", "issue_assignees", "comments"] {
+        tx.execute(
+            &format!(
+                "DELETE FROM {} WHERE repo = ?1 AND issue_number = ?2",
+                table
+            ),
+            params![key, number],
+        )?;
+    }
+
+    Ok(())
+}
+
+fn write_repo(tx: &Transaction, key: &str, cached: &CachedIssues) -> rusqlite::Result<()> {
+    write_repo_metadata(tx, key, cached)?;
+
+    for table in ["issues", "issue_labels", "issue_assignees", "comments"] {
+        tx.execute(
+            &format!("DELETE FROM {} WHERE repo = ?1", table),
+            params![key],
+        )?;
+    }
+
+    for issue in &cached.issues {
+        write_issue(tx, key, issue)?;
+    }
+
+    Ok(())
+}
+
+fn write_repo_metadata(tx: &Transaction, key: &str, cached: &CachedIssues) -> rusqlite::Result<()> {
+    let validators = cached.validators.as_ref();
+
+    tx.execute(
+        "INSERT OR REPLACE INTO repos
+            (key, last_synced, last_changed, loaded, truncated, validator_route, etag, last_modified,
+             listed_until)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+        params![
+            key,
+            cached.last_updated.to_rfc3339(),
+            cached.last_changed.to_rfc3339(),
+            cached.loaded.as_str(),
+            cached.truncated,
+            validators.map(|v| v.route.as_str()),
+            validators.and_then(|v| v.etag.as_deref()),
+            validators.and_then(|v| v.last_modified.as_deref()),
+            cached.listed_until.map(|t| t.to_rfc3339()),
+        ],
+    )?;
+
+    Ok(())
+}
+
+fn write_issue(tx: &Transaction, key: &str, issue: &IssueData) -> rusqlite::Result<()> {
+    tx.execute(
+        "INSERT OR REPLACE INTO issues
+            (repo, number, title, state, created_at, updated_at, body, creator, is_pull_request,
+             state_reason, milestone, comments_count, comments_loaded, comments_error, reactions,
+             pending, locked, lock_reason, pinned)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18,
+                 ?19)",
+        params![
+            key,
+            issue.number,
+            issue.title,
+            issue.state,
+            issue.created_at,
+            issue.updated_at,
+            issue.body,
+            issue.creator,
+            issue.is_pull_request,
+            issue.state_reason,
+            to_json(&issue.milestone),
+            issue.comments_count,
+            issue.comments_loaded,
+            issue.comments_error,
+            to_json(&issue.reactions),
+            issue.pending,
+            issue.locked,
+            issue.lock_reason,
+            issue.pinned,
+        ],
+    )?;
+
+    for table in ["issue_labels", "issue_assignees", "comments"] {
+        tx.execute(
+            &format!(
+                "DELETE FROM {} WHERE repo = ?1 AND issue_number = ?2",
+                table
+            ),
+            params![key, issue.number],
+        )?;
+    }
+
+    for (position, label) in issue.labels.iter().enumerate() {
+        tx.execute(
+            "INSERT INTO issue_labels (repo, issue_number, position, name) VALUES (?1, ?2, ?3, ?4)",
+            params![key, issue.number, position, label],
+        )?;
+    }
+
+    for (position, login) in issue.assignees.iter().enumerate() {
+        tx.execute(
+            "INSERT INTO issue_assignees (repo, issue_number, position, login) VALUES (?1, ?2, ?3, ?4)",
+            params![key, issue.number, position, login],
+        )?;
+    }
+
+    for (position, comment) in issue.comments.iter().enumerate() {
+        tx.execute(
+            "INSERT INTO comments
+                (repo, issue_number, id, position, body, created_at, updated_at, author, reactions,
+                 pending)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            params![
+                key,
+                issue.number,
+                comment.id,
+                position,
+                comment.body,
+                comment.created_at,
+                comment.updated_at,
+                comment.author,
+                to_json(&comment.reactions),
+                comment.pending,
+            ],
+        )?;
+    }
+
+    Ok(())
+}
+
+fn load_repos(conn: &Connection) -> rusqlite::Result<HashMap<String, CachedIssues>> {
+    let mut stmt = conn.prepare(
+        "SELECT key, last_synced, last_changed, loaded, truncated, validator_route, etag, last_modified,
+            listed_until
+         FROM repos",
+    )?;
+
+    let rows = stmt.query_map([], |row| {
+        let route: Option<String> = row.get(5)?;
+        let etag: Option<String> = row.get(6)?;
+        let last_modified: Option<String> = row.get(7)?;
+        let loaded: String = row.get(3)?;
+        let listed_until: Option<String> = row.get(8)?;
+
+        Ok((
+            row.get::<_, String>(0)?,
+            CachedIssues {
+                issues: Vec::new(),
+                last_updated: parse_timestamp(&row.get::<_, String>(1)?),
+                last_changed: parse_timestamp(&row.get::<_, String>(2)?),
+                loaded: IssueStateFilter::from_str_lossy(&loaded),
+                truncated: row.get(4)?,
+                listed_until: listed_until.as_deref().map(parse_timestamp),
+                validators: route.map(|route| Validators {
+                    route,
+                    etag,
+                    last_modified,
+                }),
+            },
+        ))
+    })?;
+
+    let mut repos = HashMap::new();
+    for row in rows {
+        let (key, mut cached) = row?;
+        cached.issues = load_issues(conn, &key)?;
+        repos.insert(key, cached);
+    }
+
+    Ok(repos)
+}
+
+fn load_issues(conn: &Connection, key: &str) -> rusqlite::Result<Vec<IssueData>> {
+    let mut labels = load_grouped(
+        conn,
+        "SELECT issue_number, name FROM issue_labels WHERE repo = ?1 ORDER BY position",
+        key,
+    )?;
+    let mut assignees = load_grouped(
+        conn,
+        "SELECT issue_number, login FROM issue_assignees WHERE repo = ?1 ORDER BY position",
+        key,
+    )?;
+
+    let mut comments: HashMap<i64, Vec<CommentData>> = HashMap::new();
+    let mut stmt = conn.prepare(
+        "SELECT issue_number, id, body, created_at, updated_at, author, reactions, pending
+         FROM comments WHERE repo = ?1 ORDER BY position",
+    )?;
+    let rows = stmt.query_map(params![key], |row| {
+        let body: String = row.get(2)?;
+        Ok((
+            row.get::<_, i64>(0)?,
+            CommentData {
+                id: row.get(1)?,
+                tasks: parse_tasks(Some(&body)),
+                body,
+                created_at: row.get(3)?,
+                updated_at: row.get(4)?,
+                author: row.get(5)?,
+                reactions: from_json(row.get(6)?),
+                pending: row.get(7)?,
+            },
+        ))
+    })?;
+    for row in rows {
+        let (number, comment) = row?;
+        comments.entry(number).or_default().push(comment);
+    }
+
+    let mut stmt = conn.prepare(
+        "SELECT number, title, state, created_at, updated_at, body, creator, is_pull_request,
+                state_reason, milestone, comments_count, comments_loaded, comments_error,
+                reactions, pending, locked, lock_reason, pinned
+         FROM issues WHERE repo = ?1 ORDER BY updated_at DESC",
+    )?;
+    let rows = stmt.query_map(params![key], |row| {
+        let number: i64 = row.get(0)?;
+        let body: Option<String> = row.get(5)?;
+        Ok(IssueData {
+            number,
+            title: row.get(1)?,
+            state: row.get(2)?,
+            created_at: row.get(3)?,
+            updated_at: row.get(4)?,
+            tasks: parse_tasks(body.as_deref()),
+            body,
+            labels: labels.remove(&number).unwrap_or_default(),
+            assignees: assignees.remove(&number).unwrap_or_default(),
+            comments: comments.remove(&number).unwrap_or_default(),
+            comments_count: row.get(10)?,
+            comments_loaded: row.get(11)?,
+            comments_error: row.get(12)?,
+            reactions: from_json(row.get(13)?),
+            pending: row.get(14)?,
+            locked: row.get(15)?,
+            lock_reason: row.get(16)?,
+            pinned: row.get(17)?,
+            creator: row.get(6)?,
+            is_pull_request: row.get(7)?,
+            state_reason: row.get(8)?,
+            milestone: from_json(row.get(9)?),
+        })
+    })?;
+
+    rows.collect()
+}
+
+fn load_grouped(
+    conn: &Connection,
+    sql: &str,
+    key: &str,
+) -> rusqlite::Result<HashMap<i64, Vec<String>>> {
+    let mut grouped: HashMap<i64, Vec<String>> = HashMap::new();
+    let mut stmt = conn.prepare(sql)?;
+    let rows = stmt.query_map(params![key], |row| {
+        Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+    })?;
+
+    for row in rows {
+        let (number, value) = row?;
+        grouped.entry(number).or_default().push(value);
+    }
+
+    Ok(grouped)
+}
+
+fn to_json<T: Serialize>(value: &Option<T>) -> Option<String> {
+    value
+        .as_ref()
+        .and_then(|value| serde_json::to_string(value).ok())
+}
+
+fn from_json<T: DeserializeOwned>(value: Option<String>) -> Option<T> {
+    value.and_then(|value| serde_json::from_str(&value).ok())
+}
+
+fn parse_timestamp(value: &str) -> DateTime<Utc> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|t| t.with_timezone(&Utc))
+        .unwrap_or_default()
+}
@@ -0,0 +1,82 @@
+use crate::github::issues::{CommentData, IssueData};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+
+/// Something that happened to a repo's issues between two syncs.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum IssueChange {
+    NewIssue {
+        repo: String,
+        issue: IssueData,
+    },
+    NewComment {
+        repo: String,
+        issue_number: i64,
+        issue_title: String,
+        comment: CommentData,
+    },
+    StateChanged {
+        repo: String,
+        issue_number: i64,
+        issue_title: String,
+        from: String,
+        to: String,
+        state_reason: Option<String>,
+    },
+}
+
+impl IssueChange {
+    /// The Tauri event each change is emitted under.
+    pub fn event_name(&self) -> &'static str {
+        match self {
+            IssueChange::NewIssue { .. } => "issue-created",
+            IssueChange::NewComment { .. } => "comment-created",
+            IssueChange::StateChanged { .. } => "issue-state-changed",
+        }
+    }
+}
+
+/// Compares a repo's issues before and after a sync.
+pub fn diff_issues(repo: &str, before: &[IssueData], after: &[IssueData]) -> Vec<IssueChange> {
+    let before: HashMap<i64, &IssueData> =
+        before.iter().map(|issue| (issue.number, issue)).collect();
+    let mut changes = Vec::new();
+
+    for issue in after {
+        let Some(previous) = before.get(&issue.number) else {
+            changes.push(IssueChange::NewIssue {
+                repo: repo.to_string(),
+                issue: issue.clone(),
+            });
+            continue;
+        };
+
+        if previous.state != issue.state {
+            changes.push(IssueChange::StateChanged {
+                repo: repo.to_string(),
+                issue_number: issue.number,
+                issue_title: issue.title.clone(),
+                from: previous.state.clone(),
+                to: issue.state.clone(),
+                state_reason: issue.state_reason.clone(),
+            });
+        }
+
+        // Comments may not have been loaded last time, so anything older than the
+        // previous update is assumed to be known already
+        let known: HashSet<i64> = previous.comments.iter().map(|comment| comment.id).collect();
+        for comment in &issue.comments {
+            if !known.contains(&comment.id) && comment.created_at > previous.updated_at {
+                changes.push(IssueChange::NewComment {
+                    repo: repo.to_string(),
+                    issue_number: issue.number,
+                    issue_title: issue.title.clone(),
+                    comment: comment.clone(),
+                });
+            }
+        }
+    }
+
+    changes
+}
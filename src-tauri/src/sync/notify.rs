@@ -0,0 +1,400 @@
+use super::diff::IssueChange;
+use crate::github::get_username;
+use crate::settings::settings::load_settings;
+use chrono::{Local, NaiveTime};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tauri::{command, AppHandle, Emitter, Manager, WindowEvent};
+use tauri_plugin_notification::NotificationExt;
+
+/// Above this many notifications from one sync a single summary is shown instead.
+const MAX_NOTIFICATIONS_PER_SYNC: usize = 5;
+/// Focusing the app this soon after a notification offers to open its issues.
+const PROMPT_WINDOW_SECS: u64 = 60;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WatchedIssue {
+    pub repo: String,
+    pub number: i64,
+}
+
+/// Local times between which no notifications are shown. The range may wrap past midnight.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuietHours {
+    /// `HH:MM`
+    pub start: String,
+    /// `HH:MM`
+    pub end: String,
+}
+
+impl QuietHours {
+    fn contains(&self, now: NaiveTime) -> bool {
+        let parse = |value: &str| NaiveTime::parse_from_str(value, "%H:%M").ok();
+        let (Some(start), Some(end)) = (parse(&self.start), parse(&self.end)) else {
+            return false;
+        };
+
+        if start <= end {
+            now >= start && now < end
+        } else {
+            now >= start || now < end
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct NotificationPrefs {
+    #[serde(default)]
+    pub watched_issues: Vec<WatchedIssue>,
+    #[serde(default)]
+    pub watched_repos: Vec<String>,
+    #[serde(default)]
+    pub muted_repos: Vec<String>,
+    #[serde(default)]
+    pub quiet_hours: Option<QuietHours>,
+}
+
+impl NotificationPrefs {
+    fn watches(&self, repo: &str, number: i64) -> bool {
+        self.watched_repos
+            .iter()
+            .any(|r| r.eq_ignore_ascii_case(repo))
+            || self
+                .watched_issues
+                .iter()
+                .any(|issue| issue.repo.eq_ignore_ascii_case(repo) && issue.number == number)
+    }
+
+    fn mutes(&self, repo: &str) -> bool {
+        self.muted_repos
+            .iter()
+            .any(|r| r.eq_ignore_ascii_case(repo))
+    }
+}
+
+/// An issue a notification was about.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+struct OpenIssue {
+    repo: String,
+    number: i64,
+}
+
+/// Sent to the UI when the app is focused soon after a notification, so it can offer to
+/// open the issues. Desktop notifications don't report clicks, so focusing the app for
+/// another reason mustn't navigate away on its own.
+#[derive(Debug, Clone, Serialize)]
+struct NotificationPrompt {
+    title: String,
+    issues: Vec<OpenIssue>,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct DesktopNotifier {
+    last_shown: Arc<Mutex<Option<(NotificationPrompt, Instant)>>>,
+}
+
+#[derive(Debug)]
+struct Pending {
+    title: String,
+    body: String,
+    target: OpenIssue,
+}
+
+fn get_prefs_path(app: &AppHandle) -> Result<PathBuf, String> {
+    let config_dir = app
+        .path()
+        .app_config_dir()
+        .expect("failed to get config dir");
+
+    fs::create_dir_all(&config_dir).map_err(|e| e.to_string())?;
+
+    Ok(config_dir.join("notifications.json"))
+}
+
+fn read_prefs(app: &AppHandle) -> NotificationPrefs {
+    get_prefs_path(app)
+        .ok()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default()
+}
+
+fn write_prefs(app: &AppHandle, prefs: &NotificationPrefs) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(prefs)
+        .map_err(|e| format!("Failed to serialize notification preferences: {}", e))?;
+    fs::write(get_prefs_path(app)?, json).map_err(|e| e.to_string())
+}
+
+/// Whether `text` mentions `@login` as a whole word.
+fn mentions(text: &str, login: &str) -> bool {
+    let text = text.to_lowercase();
+    let mention = format!("@{}", login.to_lowercase());
+
+    text.match_indices(&mention).any(|(start, _)| {
+        let before = text[..start].chars().next_back();
+        let after = text[start + mention.len()..].chars().next();
+        let boundary = |ch: Option<char>| {
+            ch.is_none_or(|ch| !(ch.is_alphanumeric() || ch == '-' || ch == '_'))
+        };
+        boundary(before) && boundary(after)
+    })
+}
+
+/// Picks the changes worth a notification for this user.
+fn pending_notifications(
+    changes: &[IssueChange],
+    prefs: &NotificationPrefs,
+    login: Option<&str>,
+) -> Vec<Pending> {
+    let mentioned = |text: &str| login.is_some_and(|login| mentions(text, login));
+    let is_me = |author: &str| login.is_some_and(|login| login.eq_ignore_ascii_case(author));
+
+    changes
+        .iter()
+        .filter_map(|change| match change {
+            IssueChange::NewIssue { repo, issue } => {
+                if prefs.mutes(repo) || is_me(&issue.creator) {
+                    return None;
+                }
+                let body = issue.body.as_deref().unwrap_or_default();
+                let title = if mentioned(body) || mentioned(&issue.title) {
+                    format!("{} mentioned you in {}", issue.creator, repo)
+                } else if prefs.watches(repo, issue.number) {
+                    format!("New issue in {}", repo)
+                } else {
+                    return None;
+                };
+                Some(Pending {
+                    title,
+                    body: format!("#{} {}", issue.number, issue.title),
+                    target: OpenIssue {
+                        repo: repo.clone(),
+                        number: issue.number,
+                    },
+                })
+            }
+            IssueChange::NewComment {
+                repo,
+                issue_number,
+                issue_title,
+                comment,
+            } => {
+                if prefs.mutes(repo) || is_me(&comment.author) {
+                    return None;
+                }
+                let title = if mentioned(&comment.body) {
+                    format!("{} mentioned you on #{}", comment.author, issue_number)
+                } else if prefs.watches(repo, *issue_number) {
+                    format!("{} commented on #{}", comment.author, issue_number)
+                } else {
+                    return None;
+                };
+                Some(Pending {
+                    title,
+                    body: format!("{}: {}", issue_title, comment.body),
+                    target: OpenIssue {
+                        repo: repo.clone(),
+                        number: *issue_number,
+                    },
+                })
+            }
+            IssueChange::StateChanged {
+                repo,
+                issue_number,
+                issue_title,
+                to,
+                ..
+            } => {
+                if prefs.mutes(repo) || !prefs.watches(repo, *issue_number) {
+                    return None;
+                }
+                Some(Pending {
+                    title: format!(
+                        "#{} was {}",
+                        issue_number,
+                        if to == "open" { "reopened" } else { "closed" }
+                    ),
+                    body: format!("{} in {}", issue_title, repo),
+                    target: OpenIssue {
+                        repo: repo.clone(),
+                        number: *issue_number,
+                    },
+                })
+            }
+        })
+        .collect()
+}
+
+impl DesktopNotifier {
+    fn show(&self, app: &AppHandle, title: &str, body: &str, issues: Vec<OpenIssue>) {
+        if let Err(e) = app.notification().builder().title(title).body(body).show() {
+            println!("Failed to show notification: {}", e);
+            return;
+        }
+        if let Ok(mut last_shown) = self.last_shown.lock() {
+            let prompt = NotificationPrompt {
+                title: title.to_string(),
+                issues,
+            };
+            *last_shown = Some((prompt, Instant::now()));
+        }
+    }
+
+    /// Shows OS notifications for the changes found by a background sync.
+    pub async fn notify(&self, app: &AppHandle, changes: &[IssueChange]) {
+        if changes.is_empty() {
+            return;
+        }
+        match load_settings(app.clone()).await {
+            Ok(settings) if settings.notifications => {}
+            _ => return,
+        }
+
+        let prefs = read_prefs(app);
+        if prefs
+            .quiet_hours
+            .as_ref()
+            .is_some_and(|quiet_hours| quiet_hours.contains(Local::now().time()))
+        {
+            return;
+        }
+
+        let login = get_username(app.clone()).ok();
+        let pending = pending_notifications(changes, &prefs, login.as_deref());
+
+        if pending.len() > MAX_NOTIFICATIONS_PER_SYNC {
+            let updates = pending.len();
+            let mut issues: Vec<OpenIssue> = Vec::new();
+            let mut repos: Vec<String> = Vec::new();
+            for notification in pending {
+                if !repos.contains(&notification.target.repo) {
+                    repos.push(notification.target.repo.clone());
+                }
+                if !issues.contains(&notification.target) {
+                    issues.push(notification.target);
+                }
+            }
+            let title = match repos.as_slice() {
+                [repo] => format!("{} updates in {}", updates, repo),
+                _ => format!("{} updates in {} repos", updates, repos.len()),
+            };
+            let body = issues
+                .iter()
+                .map(|issue| format!("{}#{}", issue.repo, issue.number))
+                .collect::<Vec<_>>()
+                .join(", ");
+            self.show(app, &title, &body, issues);
+            return;
+        }
+        for notification in pending {
+            self.show(
+                app,
+                &notification.title,
+                &notification.body,
+                vec![notification.target],
+            );
+        }
+    }
+
+    /// Offers to open the issues of a notification shown shortly before the app was
+    /// brought to the front. The UI decides whether to navigate.
+    fn on_focus(&self, app: &AppHandle) {
+        let Ok(mut last_shown) = self.last_shown.lock() else {
+            return;
+        };
+        if let Some((prompt, shown_at)) = last_shown.take() {
+            if shown_at.elapsed() < Duration::from_secs(PROMPT_WINDOW_SECS) {
+                if let Err(e) = app.emit("notification-prompt", prompt) {
+                    println!("Failed to emit notification-prompt: {}", e);
+                }
+            }
+        }
+    }
+}
+
+pub fn setup_focus_prompt(app: &tauri::App) {
+    let Some(window) = app.get_webview_window("main") else {
+        return;
+    };
+    let handle = app.handle().clone();
+    window.on_window_event(move |event| {
+        if let WindowEvent::Focused(true) = event {
+            handle.state::<DesktopNotifier>().on_focus(&handle);
+        }
+    });
+}
+
+#[command]
+pub async fn get_notification_prefs(app: AppHandle) -> Result<NotificationPrefs, String> {
+    Ok(read_prefs(&app))
+}
+
+#[command]
+pub async fn set_issue_watched(
+    app: AppHandle,
+    repo: String,
+    number: i64,
+    watched: bool,
+) -> Result<NotificationPrefs, String> {
+    let mut prefs = read_prefs(&app);
+    let issue = WatchedIssue { repo, number };
+    prefs.watched_issues.retain(|watched| *watched != issue);
+    if watched {
+        prefs.watched_issues.push(issue);
+    }
+    write_prefs(&app, &prefs)?;
+    Ok(prefs)
+}
+
+#[command]
+pub async fn set_repo_watched(
+    app: AppHandle,
+    repo: String,
+    watched: bool,
+) -> Result<NotificationPrefs, String> {
+    let mut prefs = read_prefs(&app);
+    prefs
+        .watched_repos
+        .retain(|r| !r.eq_ignore_ascii_case(&repo));
+    if watched {
+        prefs.watched_repos.push(repo);
+    }
+    write_prefs(&app, &prefs)?;
+    Ok(prefs)
+}
+
+#[command]
+pub async fn set_repo_muted(
+    app: AppHandle,
+    repo: String,
+    muted: bool,
+) -> Result<NotificationPrefs, String> {
+    let mut prefs = read_prefs(&app);
+    prefs.muted_repos.retain(|r| !r.eq_ignore_ascii_case(&repo));
+    if muted {
+        prefs.muted_repos.push(repo);
+    }
+    write_prefs(&app, &prefs)?;
+    Ok(prefs)
+}
+
+/// Sets or clears the quiet hours.
+#[command]
+pub async fn set_quiet_hours(
+    app: AppHandle,
+    quiet_hours: Option<QuietHours>,
+) -> Result<NotificationPrefs, String> {
+    let mut prefs = read_prefs(&app);
+    if let Some(quiet_hours) = &quiet_hours {
+        for value in [&quiet_hours.start, &quiet_hours.end] {
+            NaiveTime::parse_from_str(value, "%H:%M")
+                .map_err(|_| format!("Invalid time, expected HH:MM: {}", value))?;
+        }
+    }
+    prefs.quiet_hours = quiet_hours;
+    write_prefs(&app, &prefs)?;
+    Ok(prefs)
+}
@@ -0,0 +1,387 @@
+use super::diff::{diff_issues, IssueChange};
+use super::notify::DesktopNotifier;
+use crate::github::comments::prefetch_comments_for;
+use crate::github::github_client::get_client;
+use crate::github::issues::{fetch_issues, get_pinned_repos, IssueData, IssuesCache};
+use crate::github::oauth::get_username;
+use crate::github::rate_limit::refresh_untracked_quotas;
+use crate::github::repos::get_repos_from_store;
+use crate::outbox::replay::flush;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use tauri::{command, AppHandle, Emitter, Manager, State};
+use tokio::net::TcpStream;
+use tokio::sync::Notify;
+
+const CONNECTIVITY_HOST: (&str, u16) = ("api.github.com", 443);
+const CONNECTIVITY_TIMEOUT_SECS: u64 = 5;
+/// Comment loads per repo and sync, so a busy repo can't use up the rate limit the rest of
+/// the app shares. Issues past the cap load their comments when opened.
+const MAX_COMMENT_LOADS_PER_SYNC: usize = 30;
+
+fn default_enabled() -> bool {
+    true
+}
+
+fn default_interval() -> u64 {
+    300
+}
+
+fn default_jitter() -> u64 {
+    30
+}
+
+fn default_offline_retry() -> u64 {
+    60
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncSettings {
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// Seconds between syncs of the pinned and stored repos.
+    #[serde(default = "default_interval")]
+    pub interval_secs: u64,
+    /// Up to this many seconds are added to each interval so syncs don't line up.
+    #[serde(default = "default_jitter")]
+    pub jitter_secs: u64,
+    /// How often to check whether the machine is back online.
+    #[serde(default = "default_offline_retry")]
+    pub offline_retry_secs: u64,
+}
+
+impl Default for SyncSettings {
+    fn default() -> Self {
+        Self {
+            enabled: default_enabled(),
+            interval_secs: default_interval(),
+            jitter_secs: default_jitter(),
+            offline_retry_secs: default_offline_retry(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SyncStatus {
+    pub running: bool,
+    pub online: bool,
+    pub last_run: Option<String>,
+    pub next_run: Option<String>,
+    pub repos: Vec<RepoSyncResult>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RepoSyncResult {
+    pub repo: String,
+    pub changes: usize,
+    pub error: Option<String>,
+}
+
+/// Sent after every repo sync, whether or not anything changed.
+#[derive(Debug, Clone, Serialize)]
+struct RepoSynced {
+    repo: String,
+    changes: Vec<IssueChange>,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct SyncScheduler {
+    status: Arc<Mutex<SyncStatus>>,
+    wake: Arc<Notify>,
+}
+
+fn get_sync_settings_path(app: &AppHandle) -> Result<PathBuf, String> {
+    let config_dir = app
+        .path()
+        .app_config_dir()
+        .expect("failed to get config dir");
+
+    fs::create_dir_all(&config_dir).map_err(|e| e.to_string())?;
+
+    Ok(config_dir.join("sync.json"))
+}
+
+fn read_sync_settings(app: &AppHandle) -> SyncSettings {
+    get_sync_settings_path(app)
+        .ok()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default()
+}
+
+fn jitter(max_secs: u64) -> std::time::Duration {
+    let nanos = Utc::now().timestamp_subsec_nanos() as u64;
+    std::time::Duration::from_millis(nanos % (max_secs * 1000 + 1))
+}
+
+/// A cheap reachability check, so an offline machine doesn't burn through retries.
+pub(crate) async fn is_online() -> bool {
+    matches!(
+        tokio::time::timeout(
+            std::time::Duration::from_secs(CONNECTIVITY_TIMEOUT_SECS),
+            TcpStream::connect(CONNECTIVITY_HOST),
+        )
+        .await,
+        Ok(Ok(_))
+    )
+}
+
+/// Pinned repos first, then the rest of the stored ones, without duplicates. The app
+/// stores bare repo names for the signed-in user's repos, so those get their login as owner
+/// to match the cache keys the UI uses.
+async fn repos_to_sync(app: &AppHandle) -> Vec<String> {
+    let pinned = get_pinned_repos(app.clone()).await.unwrap_or_default();
+    let stored = get_repos_from_store(app.clone()).await.unwrap_or_default();
+    let login = get_username(app.clone()).ok();
+
+    let mut repos: Vec<String> = Vec::new();
+    for repo in pinned.into_iter().chain(stored) {
+        let repo = match (repo.contains('/'), &login) {
+            (true, _) => repo,
+            (false, Some(login)) if !repo.is_empty() => format!("{}/{}", login, repo),
+            _ => continue,
+        };
+        if !repos.contains(&repo) {
+            repos.push(repo);
+        }
+    }
+    repos
+}
+
+fn snapshot(cache: &IssuesCache, key: &str) -> Option<Vec<IssueData>> {
+    let cache_guard = cache.get_cache().lock().ok()?;
+    cache_guard.get(key).map(|cached| cached.issues.clone())
+}
+
+/// Syncs one repo, loads comments for changed issues and emits what changed.
+async fn sync_repo(app: &AppHandle, repo: &str) -> Result<usize, String> {
+    let (owner, name) = repo
+        .split_once('/')
+        .ok_or_else(|| format!("Invalid repo name: {}", repo))?;
+    let cache = app.state::<IssuesCache>().inner().clone();
+
+    let before = snapshot(&cache, repo);
+    let state = cache
+        .get_cache()
+        .lock()
+        .map_err(|e| e.to_string())?
+        .get(repo)
+        .map(|cached| cached.loaded);
+
+    // Comments are loaded here instead of in the background so they make it into the diff
+    fetch_issues(
+        owner.to_string(),
+        name.to_string(),
+        app.state::<IssuesCache>(),
+        true,
+        state,
+        None,
+        Some(false),
+        None,
+        app.clone(),
+    )
+    .await?;
+
+    // A repo seen for the first time has no history to compare against, so its comments
+    // are left for when the issues are opened
+    let Some(before) = before else {
+        return Ok(0);
+    };
+
+    // Only issues this sync changed, newest first
+    let known: HashMap<i64, (&str, i64)> = before
+        .iter()
+        .map(|issue| {
+            (
+                issue.number,
+                (issue.updated_at.as_str(), issue.comments_count),
+            )
+        })
+        .collect();
+    let mut changed: Vec<IssueData> = snapshot(&cache, repo)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|issue| !issue.comments_loaded && !issue.pending)
+        .filter(|issue| {
+            known
+                .get(&issue.number)
+                .is_none_or(|&(updated_at, comments_count)| {
+                    updated_at != issue.updated_at || comments_count != issue.comments_count
+                })
+        })
+        .collect();
+    changed.sort_by(|a, b| b.updated_at.cmp(&a.updated_at));
+    let pending: Vec<i64> = changed
+        .iter()
+        .take(MAX_COMMENT_LOADS_PER_SYNC)
+        .map(|issue| issue.number)
+        .collect();
+    if !pending.is_empty() {
+        let octocrab = get_client()?;
+        prefetch_comments_for(&cache, &octocrab, owner, name, pending).await;
+    }
+    let after = snapshot(&cache, repo).unwrap_or_default();
+    let changes = diff_issues(repo, &before, &after);
+
+    for change in &changes {
+        if let Err(e) = app.emit(change.event_name(), change) {
+            println!("Failed to emit {}: {}", change.event_name(), e);
+        }
+    }
+    let notifier = app.state::<DesktopNotifier>().inner().clone();
+    notifier.notify(app, &changes).await;
+    let count = changes.len();
+    if let Err(e) = app.emit(
+        "repo-synced",
+        RepoSynced {
+            repo: repo.to_string(),
+            changes,
+        },
+    ) {
+        println!("Failed to emit repo-synced: {}", e);
+    }
+
+    Ok(count)
+}
+
+impl SyncScheduler {
+    fn update_status(&self, app: &AppHandle, update: impl FnOnce(&mut SyncStatus)) {
+        let status = match self.status.lock() {
+            Ok(mut status) => {
+                update(&mut status);
+                status.clone()
+            }
+            Err(_) => return,
+        };
+        if let Err(e) = app.emit("sync-status", status) {
+            println!("Failed to emit sync-status: {}", e);
+        }
+    }
+
+    async fn run_once(&self, app: &AppHandle) {
+        self.update_status(app, |status| status.running = true);
+
+        let mut results = Vec::new();
+        for repo in repos_to_sync(app).await {
+            let result = sync_repo(app, &repo).await;
+            if let Err(e) = &result {
+                println!("Background sync of {} failed: {}", repo, e);
+            }
+            results.push(RepoSyncResult {
+                repo,
+                changes: *result.as_ref().unwrap_or(&0),
+                error: result.err(),
+            });
+        }
+        refresh_untracked_quotas().await;
+
+        self.update_status(app, |status| {
+            status.running = false;
+            status.last_run = Some(Utc::now().to_rfc3339());
+            status.repos = results;
+        });
+    }
+
+    /// Waits for `duration`, or until a manual sync or a settings change wakes the scheduler.
+    async fn sleep(&self, app: &AppHandle, duration: std::time::Duration) {
+        let next_run: DateTime<Utc> =
+            Utc::now() + Duration::from_std(duration).unwrap_or_else(|_| Duration::zero());
+        self.update_status(app, |status| status.next_run = Some(next_run.to_rfc3339()));
+
+        tokio::select! {
+            _ = tokio::time::sleep(duration) => {}
+            _ = self.wake.notified() => {}
+        }
+    }
+
+    /// Runs forever, syncing every repo on the configured interval.
+    pub async fn run(self, app: AppHandle) {
+        loop {
+            let settings = read_sync_settings(&app);
+            if !settings.enabled {
+                self.update_status(&app, |status| status.next_run = None);
+                self.wake.notified().await;
+                continue;
+            }
+
+            // Without a signed-in client there is nothing to sync yet
+            if get_client().is_err() {
+                self.sleep(
+                    &app,
+                    std::time::Duration::from_secs(settings.offline_retry_secs),
+                )
+                .await;
+                continue;
+            }
+
+            let online = is_online().await;
+            self.update_status(&app, |status| status.online = online);
+            if !online {
+                println!("Offline, pausing background sync");
+                self.sleep(
+                    &app,
+                    std::time::Duration::from_secs(settings.offline_retry_secs),
+                )
+                .await;
+                continue;
+            }
+
+            // Writes made offline go out before the sync picks up their results
+            if let Err(e) = flush(&app).await {
+                println!("Failed to send queued writes: {}", e);
+            }
+            self.run_once(&app).await;
+
+            let interval = std::time::Duration::from_secs(settings.interval_secs.max(60))
+                + jitter(settings.jitter_secs);
+            self.sleep(&app, interval).await;
+        }
+    }
+}
+
+/// Starts the background sync loop.
+pub fn start(app: &AppHandle) {
+    let scheduler = app.state::<SyncScheduler>().inner().clone();
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        scheduler.run(app).await;
+    });
+}
+
+#[command]
+pub async fn get_sync_settings(app: AppHandle) -> Result<SyncSettings, String> {
+    Ok(read_sync_settings(&app))
+}
+
+#[command]
+pub async fn save_sync_settings(
+    app: AppHandle,
+    settings: SyncSettings,
+    scheduler: State<'_, SyncScheduler>,
+) -> Result<(), String> {
+    let path = get_sync_settings_path(&app)?;
+    let json = serde_json::to_string_pretty(&settings)
+        .map_err(|e| format!("Failed to serialize sync settings: {}", e))?;
+    fs::write(&path, json).map_err(|e| e.to_string())?;
+
+    // Pick up the new interval straight away
+    scheduler.wake.notify_one();
+    Ok(())
+}
+
+#[command]
+pub async fn get_sync_status(scheduler: State<'_, SyncScheduler>) -> Result<SyncStatus, String> {
+    let status = scheduler.status.lock().map_err(|e| e.to_string())?;
+    Ok(status.clone())
+}
+
+/// Syncs every repo now instead of waiting for the next interval.
+#[command]
+pub async fn trigger_sync(scheduler: State<'_, SyncScheduler>) -> Result<(), String> {
+    scheduler.wake.notify_one();
+    Ok(())
+}